@@ -0,0 +1,92 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Acknowledging latched alarms, by pressing their pad or with `SIGHUP`.
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use midir::{Ignore, MidiInput, MidiInputConnection};
+
+use crate::utility::{midi_note_to_pad, Pad, PADS};
+
+/// Presses of each pad, in pad order.
+static PRESSES: [AtomicUsize; 8] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+/// Acknowledgements of every pad at once.
+static ALL: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(unix)]
+extern "C" fn on_hangup(_signal: libc::c_int) {
+    ALL.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Acknowledges every alarm whenever the process gets `SIGHUP`, e.g. from
+/// `pkill -HUP lpd8_monitor`.
+#[cfg(unix)]
+pub fn listen_for_hangups() {
+    // SAFETY: the handler only bumps an atomic counter, which is
+    // async-signal-safe.
+    unsafe {
+        libc::signal(
+            libc::SIGHUP,
+            on_hangup as *const () as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(not(unix))]
+pub const fn listen_for_hangups() {}
+
+/// Acknowledges a pad's alarm whenever the pad is pressed, for as long as
+/// the returned connection is kept. `None` if the LPD8 can't be listened
+/// to.
+pub fn listen_for_presses() -> Option<MidiInputConnection<()>> {
+    let mut midi_in = MidiInput::new("Acknowledge").ok()?;
+    midi_in.ignore(Ignore::All);
+    let port = midi_in.ports().into_iter().find(|port| {
+        midi_in
+            .port_name(port)
+            .is_ok_and(|name| name.contains("LPD8"))
+    })?;
+    midi_in
+        .connect(
+            &port,
+            "Acknowledge",
+            |_, message, ()| {
+                // A note on with a velocity of 0 is really a note off.
+                if let &[status, note, velocity] = message {
+                    if status & 0xF0 == 0x90 && velocity > 0 {
+                        if let Some(pad) = midi_note_to_pad(note) {
+                            PRESSES[index(pad)]
+                                .fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+            },
+            (),
+        )
+        .map_err(|err| eprintln!("Couldn't listen to the LPD8: {err}"))
+        .ok()
+}
+
+/// How many times any of `pads` has been acknowledged so far.
+pub fn count(pads: &[Pad]) -> usize {
+    pads.iter()
+        .map(|&pad| PRESSES[index(pad)].load(Ordering::Relaxed))
+        .sum::<usize>()
+        + ALL.load(Ordering::Relaxed)
+}
+
+fn index(pad: Pad) -> usize {
+    PADS.iter().position(|&other| other == pad).unwrap_or(0)
+}
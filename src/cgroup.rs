@@ -0,0 +1,46 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! CPU accounting of cgroup v2 groups, read from `/sys/fs/cgroup`.
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+/// `path` as given if it's absolute, otherwise relative to the cgroup
+/// root.
+pub fn resolve(path: &Path) -> PathBuf {
+    Path::new("/sys/fs/cgroup").join(path)
+}
+
+/// Whether `group` exists and has CPU accounting.
+pub fn exists(group: &Path) -> bool { group.join("cpu.stat").is_file() }
+
+/// CPU time the processes in `group` have used, in microseconds. `None`
+/// while the group doesn't exist, e.g. because its slice is restarting.
+pub fn cpu_usage_usec(group: &Path) -> Option<u64> {
+    read_to_string(group.join("cpu.stat"))
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// How many CPUs' worth of time `group` may use, from its `cpu.max` quota.
+/// Without a quota, that's every CPU the process can run on.
+#[allow(clippy::cast_precision_loss)]
+pub fn cpu_limit(group: &Path) -> f32 {
+    let quota = read_to_string(group.join("cpu.max")).ok().and_then(|max| {
+        // `<quota> <period>` in microseconds, or `max <period>`.
+        let mut fields = max.split_whitespace();
+        let quota: u64 = fields.next()?.parse().ok()?;
+        let period: u64 = fields.next()?.parse().ok()?;
+        (period > 0).then(|| quota as f32 / period as f32)
+    });
+    quota.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map_or(1.0, |cpus| cpus.get() as f32)
+    })
+}
@@ -0,0 +1,138 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Pad colors, for the LPD8 MK2's RGB pads.
+use std::convert::TryFrom;
+
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct Rgb {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    /// Reads colors written like `"#ff8000"`.
+    fn try_from(hex: String) -> Result<Self, String> {
+        let digits = hex.strip_prefix('#').unwrap_or(&hex);
+        let component = |at: usize| {
+            digits
+                .get(at..at + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        };
+        match (digits.len(), component(0), component(2), component(4)) {
+            (6, Some(red), Some(green), Some(blue)) => {
+                Ok(Self { red, green, blue })
+            }
+            _ => Err(format!("{hex:?} isn't a color like \"#ff8000\"")),
+        }
+    }
+}
+
+/// Green, through yellow, to red.
+pub const DEFAULT_GRADIENT: [Rgb; 3] = [
+    Rgb {
+        red: 0,
+        green: 255,
+        blue: 0,
+    },
+    Rgb {
+        red: 255,
+        green: 255,
+        blue: 0,
+    },
+    Rgb {
+        red: 255,
+        green: 0,
+        blue: 0,
+    },
+];
+
+/// The color `value` lands on, going from the first of `stops` at 0 to
+/// the last at 1, with the rest spread evenly in between.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+pub fn gradient(stops: &[Rgb], value: f32) -> Rgb {
+    match stops {
+        [] => Rgb::default(),
+        [only] => *only,
+        _ => {
+            let position = value.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+            let index = (position as usize).min(stops.len() - 2);
+            let (from, to) = (stops[index], stops[index + 1]);
+            let along = position - index as f32;
+            let mix = |from: u8, to: u8| {
+                (f32::from(to) - f32::from(from))
+                    .mul_add(along, f32::from(from))
+                    .round() as u8
+            };
+            Rgb {
+                red: mix(from.red, to.red),
+                green: mix(from.green, to.green),
+                blue: mix(from.blue, to.blue),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn rgb(red: u8, green: u8, blue: u8) -> Rgb {
+        Rgb { red, green, blue }
+    }
+
+    const BLACK: Rgb = rgb(0, 0, 0);
+    const WHITE: Rgb = rgb(255, 255, 255);
+    const RED: Rgb = rgb(255, 0, 0);
+    const BLUE: Rgb = rgb(0, 0, 255);
+
+    #[test]
+    fn endpoints_are_the_first_and_last_stops() {
+        assert_eq!(gradient(&[RED, BLACK, BLUE], 0.0), RED);
+        assert_eq!(gradient(&[RED, BLACK, BLUE], 1.0), BLUE);
+    }
+
+    #[test]
+    fn midpoint_of_two_stops_is_their_mix() {
+        assert_eq!(gradient(&[BLACK, WHITE], 0.5), rgb(128, 128, 128));
+        assert_eq!(gradient(&[RED, BLUE], 0.5), rgb(128, 0, 128));
+    }
+
+    #[test]
+    fn midpoint_of_three_stops_is_the_middle_one() {
+        assert_eq!(gradient(&[RED, WHITE, BLUE], 0.5), WHITE);
+        assert_eq!(gradient(&[RED, WHITE, BLUE], 0.25), rgb(255, 128, 128));
+        assert_eq!(gradient(&[RED, WHITE, BLUE], 0.75), rgb(128, 128, 255));
+    }
+
+    #[test]
+    fn single_stop_is_used_throughout() {
+        for value in [0.0, 0.5, 1.0] {
+            assert_eq!(gradient(&[BLUE], value), BLUE);
+        }
+    }
+
+    #[test]
+    fn no_stops_are_dark() {
+        assert_eq!(gradient(&[], 0.5), Rgb::default());
+    }
+
+    #[test]
+    fn values_out_of_range_are_clamped() {
+        assert_eq!(gradient(&[RED, BLUE], -1.0), RED);
+        assert_eq!(gradient(&[RED, BLUE], 2.0), BLUE);
+        assert_eq!(gradient(&[RED, BLUE], f32::INFINITY), BLUE);
+    }
+}
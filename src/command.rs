@@ -0,0 +1,57 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+use std::io::Read;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+/// Runs `command` with `sh -c` and returns its stdout, or `None` if it
+/// failed or was killed for taking longer than `timeout`.
+pub fn run(command: &str, timeout: Duration) -> Option<String> {
+    output(Command::new("sh").args(["-c", command]), timeout)
+}
+
+/// Like [`run`], for a program that doesn't need a shell.
+pub fn output(command: &mut Command, timeout: Duration) -> Option<String> {
+    let (status, output) = output_with_status(command, timeout)?;
+    status.success().then_some(output)
+}
+
+/// Like [`output`], for a program whose exit status means more than
+/// success or failure. Only returns `None` if it couldn't be run or was
+/// killed.
+pub fn output_with_status(
+    command: &mut Command,
+    timeout: Duration,
+) -> Option<(ExitStatus, String)> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    // Read on another thread, so a command with a lot of output can't fill
+    // up the pipe and stall while we wait for it to exit.
+    let mut stdout = child.stdout.take()?;
+    let reader = spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).ok().map(|_| output)
+    });
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().ok()? {
+            break status;
+        }
+        if start.elapsed() > timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
+        sleep(Duration::from_millis(10));
+    };
+    let output = reader.join().ok()??;
+    Some((status, output))
+}
@@ -0,0 +1,613 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::de::value::{MapAccessDeserializer, MapDeserializer};
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use sysinfo::{RefreshKind, System};
+
+use crate::acknowledge;
+use crate::dimming::{Idle, Night};
+use crate::history::History;
+use crate::metric::{
+    default_temperature_sensor, MemoryMode, Metric, WarnOnce,
+};
+use crate::render::Show;
+use crate::rotation::Rotation;
+use crate::style::Style;
+use crate::utility::{LocalTime, Pad, PADS};
+
+/// Settings read from the TOML config file.
+///
+/// Pads that aren't mentioned in the file keep their default metric.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub device: Device,
+    #[serde(default)]
+    pub idle: Option<Idle>,
+    #[serde(default)]
+    pub night: Vec<Night>,
+    /// Most times a second any pad may flash, however it's set up.
+    #[serde(default = "default_max_blink_hz")]
+    pub max_blink_hz: f32,
+    /// Seconds each page stays up for before the next one's turn.
+    #[serde(default = "default_page_seconds")]
+    pub page_seconds: f32,
+    #[serde(default)]
+    display: Style,
+    #[serde(default)]
+    pads: BTreeMap<Pad, PadConfig>,
+    /// Layouts the pads switch between, instead of the one in `pads`.
+    #[serde(default)]
+    pages: Vec<Page>,
+}
+
+/// A `[[pages]]` table: a layout of its own, with pads set up just like
+/// outside of one.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Page {
+    #[serde(default)]
+    pads: BTreeMap<Pad, PadConfig>,
+}
+
+const fn default_max_blink_hz() -> f32 { 4.0 }
+
+const fn default_page_seconds() -> f32 { 30.0 }
+
+/// What it'd be for an empty file, for when there's no file at all.
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            device: Device::default(),
+            idle: None,
+            night: vec![],
+            max_blink_hz: default_max_blink_hz(),
+            page_seconds: default_page_seconds(),
+            display: Style::default(),
+            pads: BTreeMap::new(),
+            pages: vec![],
+        }
+    }
+}
+
+/// Which LPD8 the pads are on.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Device {
+    #[default]
+    Lpd8,
+    /// The MK2, whose pads are colored as well as lit.
+    Lpd8Mk2,
+}
+
+/// A `[pads.N]` table: the metric, its parameters, and any display
+/// settings, side by side.
+#[derive(Deserialize)]
+struct PadConfig {
+    #[serde(flatten)]
+    style: Style,
+    /// Further pads the value is spread across, in order, as a bar graph.
+    #[serde(default)]
+    bar: Vec<Pad>,
+    /// Further pads showing what this one did a step ago, two steps ago,
+    /// and so on.
+    #[serde(default)]
+    history: Vec<Pad>,
+    /// Seconds between the steps of the history.
+    #[serde(default = "default_history_step")]
+    history_step: f32,
+    /// Further metrics taking turns with this one.
+    #[serde(default)]
+    metrics: Vec<RotatedMetric>,
+    /// Seconds each metric is shown for before the next one's turn.
+    #[serde(default = "default_dwell")]
+    dwell: f32,
+    /// Whatever the display settings leave over, so a misspelled one is an
+    /// error rather than quietly ignored.
+    #[serde(flatten, deserialize_with = "strict_metric")]
+    metric: Metric,
+}
+
+const fn default_history_step() -> f32 { 2.0 }
+
+const fn default_dwell() -> f32 { 3.0 }
+
+/// An entry in `metrics`: either just the name of a metric, for those
+/// without parameters or happy with the defaults, or a table like a pad's,
+/// with a `metric` key and parameters.
+struct RotatedMetric(Metric);
+
+impl<'de> Deserialize<'de> for RotatedMetric {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct NameOrTable;
+
+        impl<'de> Visitor<'de> for NameOrTable {
+            type Value = Metric;
+
+            fn expecting(
+                &self,
+                formatter: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                formatter.write_str("a metric name or table")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<Metric, E> {
+                Metric::deserialize(MapDeserializer::new(std::iter::once((
+                    "metric", name,
+                ))))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                table: A,
+            ) -> Result<Metric, A::Error> {
+                strict_metric(MapAccessDeserializer::new(table))
+            }
+        }
+
+        deserializer.deserialize_any(NameOrTable).map(Self)
+    }
+}
+
+/// Reads a metric, and nothing but, out of a table of settings.
+fn strict_metric<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Metric, D::Error> {
+    let table = toml::Table::deserialize(deserializer)?;
+    let metric = Metric::deserialize(toml::Value::Table(table.clone()))
+        .map_err(de::Error::custom)?;
+    match table.keys().find(|key| *key != "metric") {
+        Some(key) if !metric.has_parameters() => Err(de::Error::custom(
+            format!("unknown field `{key}`, the metric has no settings"),
+        )),
+        _ => Ok(metric),
+    }
+}
+
+impl PadConfig {
+    /// Checks the pad's metric, and any it takes turns with.
+    fn validate_metrics(&self) -> Result<(), String> {
+        if !self.metrics.is_empty() {
+            if !self.history.is_empty() {
+                return Err("can't have both metrics and a history".into());
+            }
+            if !(self.dwell > 0.0 && self.dwell.is_finite()) {
+                return Err("dwell must be positive".into());
+            }
+        }
+        let rotated = self.metrics.iter().map(|RotatedMetric(metric)| metric);
+        for metric in std::iter::once(&self.metric).chain(rotated) {
+            metric.validate()?;
+            if metric.is_driven() && !self.metrics.is_empty() {
+                return Err("clock and heartbeat can't take turns with \
+                            other metrics"
+                    .into());
+            }
+            if self.style.pulses() && !metric.counts_events() {
+                return Err("mode = \"pulse\" needs a metric that counts \
+                            events"
+                    .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// The pads after the first one this pad's metric is shown on.
+    fn followers(&self) -> &[Pad] {
+        if self.history.is_empty() {
+            &self.bar
+        } else {
+            &self.history
+        }
+    }
+}
+
+/// A metric and the pads showing it: just the one, a bar graph, or a
+/// history.
+pub struct Group {
+    pub pads: Vec<Pad>,
+    pub metric: Metric,
+    pub style: Style,
+    pub history: Option<History>,
+    rotation: Option<Rotation>,
+    /// Acknowledgements of the group's pads so far.
+    acknowledged: usize,
+}
+
+impl Group {
+    /// What each of the group's pads shows, now that its metric reads
+    /// `value`.
+    fn show(&mut self, value: f32) -> Vec<Show> {
+        let acknowledged = acknowledge::count(&self.pads);
+        if acknowledged != self.acknowledged {
+            self.acknowledged = acknowledged;
+            let mut acknowledged = self.style.acknowledge();
+            if let Some(rotation) = &mut self.rotation {
+                for style in rotation.styles_mut() {
+                    acknowledged |= style.acknowledge();
+                }
+            }
+            if acknowledged {
+                self.log("alarm acknowledged");
+            }
+        }
+        let was_latched = self.style.latched();
+        let events = self.metric.events();
+        let shows = match &mut self.history {
+            Some(history) => {
+                history.record(self.style.apply(value, events, 1).remove(0))
+            }
+            None => self.style.apply(value, events, self.pads.len()),
+        };
+        if !was_latched && self.style.latched() {
+            self.log("alarm raised");
+        }
+        shows
+    }
+
+    /// Samples the group's metrics and works out what each of its pads
+    /// shows.
+    pub fn sample(&mut self, system: &System) -> Vec<Show> {
+        let value = self.metric.sample(system);
+        let shows = self.show(value);
+        match &mut self.rotation {
+            Some(rotation) => rotation.pick(shows, system),
+            None => shows,
+        }
+    }
+
+    /// Every metric the group shows.
+    pub fn metrics(&self) -> impl Iterator<Item = &Metric> {
+        std::iter::once(&self.metric)
+            .chain(self.rotation.iter().flat_map(Rotation::metrics))
+    }
+
+    /// Adds whatever `sysinfo` needs to refresh for the group's metrics.
+    pub fn refresh_kind(&self, kind: RefreshKind) -> RefreshKind {
+        self.metrics()
+            .fold(kind, |kind, metric| metric.refresh_kind(kind))
+    }
+
+    /// Display settings of the metric showing right now.
+    pub fn current_style(&self) -> &Style {
+        self.rotation
+            .as_ref()
+            .and_then(Rotation::current)
+            .unwrap_or(&self.style)
+    }
+
+    fn log(&self, what: &str) {
+        let pad = self.pads[0];
+        match LocalTime::now() {
+            Some(now) => println!("{now}: {pad:?}: {what}"),
+            None => println!("{pad:?}: {what}"),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = read_to_string(path).map_err(|err| {
+            format!("Could not read {}: {}", path.display(), err)
+        })?;
+        let mut config: Self = toml::from_str(&contents).map_err(|err| {
+            format!("Invalid config {}: {}", path.display(), err)
+        })?;
+        config
+            .display
+            .validate()
+            .and_then(|()| {
+                if config.display.pulses() {
+                    Err("mode = \"pulse\" can only be set on a pad".into())
+                } else {
+                    Ok(())
+                }
+            })
+            .map_err(|err| {
+                format!("Invalid config {}: display: {}", path.display(), err)
+            })?;
+        config
+            .idle
+            .as_ref()
+            .map_or(Ok(()), Idle::validate)
+            .and_then(|()| config.night.iter().try_for_each(Night::validate))
+            .and_then(|()| {
+                if config.max_blink_hz > 0.0 && config.max_blink_hz <= 50.0 {
+                    Ok(())
+                } else {
+                    Err("max_blink_hz must be above 0 and at most 50".into())
+                }
+            })
+            .and_then(|()| {
+                if !config.pages.is_empty() && !config.pads.is_empty() {
+                    Err("with [[pages]], pads go in the pages".into())
+                } else if !(config.page_seconds > 0.0
+                    && config.page_seconds.is_finite())
+                {
+                    Err("page_seconds must be positive".into())
+                } else {
+                    Ok(())
+                }
+            })
+            .map_err(|err| {
+                format!("Invalid config {}: {}", path.display(), err)
+            })?;
+        if config.pages.is_empty() {
+            let pads = std::mem::take(&mut config.pads);
+            config.pages.push(Page { pads });
+        }
+        let several = config.pages.len() > 1;
+        for (number, page) in (1..).zip(&config.pages) {
+            let which = if several {
+                format!("page {number}: ")
+            } else {
+                String::new()
+            };
+            for (pad, pad_config) in &page.pads {
+                pad_config
+                    .style
+                    .validate()
+                    .and_then(|()| page.validate_followers(*pad))
+                    .and_then(|()| pad_config.validate_metrics())
+                    .map_err(|err| {
+                        format!(
+                            "Invalid config {}: {}{:?}: {}",
+                            path.display(),
+                            which,
+                            pad,
+                            err
+                        )
+                    })?;
+            }
+        }
+        for pad in PADS {
+            let mut styles = config
+                .pages
+                .iter()
+                .map(|page| page.style_of(pad, &config.display));
+            let first = styles.next().unwrap_or_default();
+            if styles.any(|style| !style.runs_like(&first)) {
+                return Err(format!(
+                    "Invalid config {}: {:?}: period, quantize, dither, \
+                     dither_seed and phase have to be the same on every page",
+                    path.display(),
+                    pad
+                ));
+            }
+        }
+        Ok(config)
+    }
+
+    /// How many pages the pads switch between.
+    pub fn pages(&self) -> usize { self.pages.len().max(1) }
+
+    /// The layout of each page, in order.
+    pub fn into_pages(mut self) -> Vec<Vec<Group>> {
+        if self.pages.is_empty() {
+            self.pages.push(Page { pads: self.pads });
+        }
+        let display = self.display;
+        self.pages
+            .into_iter()
+            .map(|page| page.into_layout(&display))
+            .collect()
+    }
+}
+
+impl Page {
+    /// `pad`'s display settings on this page, whether it has a metric of
+    /// its own, follows another pad's, or is left at its default.
+    fn style_of(&self, pad: Pad, display: &Style) -> Style {
+        self.pads
+            .get(&pad)
+            .or_else(|| {
+                self.pads
+                    .values()
+                    .find(|config| config.followers().contains(&pad))
+            })
+            .map_or_else(Style::default, |config| config.style.clone())
+            .or(display, false)
+    }
+
+    /// Checks that the pads `pad`'s bar or history continues onto aren't
+    /// showing anything else.
+    fn validate_followers(&self, pad: Pad) -> Result<(), String> {
+        let config = &self.pads[&pad];
+        if !config.bar.is_empty() && !config.history.is_empty() {
+            return Err("can't have both a bar and a history".into());
+        }
+        if !(config.history_step > 0.0 && config.history_step.is_finite()) {
+            return Err("history_step must be positive".into());
+        }
+        let followers = config.followers();
+        for (index, &other) in followers.iter().enumerate() {
+            if other == pad || followers[..index].contains(&other) {
+                return Err(format!("{other:?} is in there more than once"));
+            }
+            if self.pads.contains_key(&other) {
+                return Err(format!(
+                    "{other:?} can't follow, it has its own metric"
+                ));
+            }
+            let taken = self.pads.iter().any(|(&owner, config)| {
+                owner < pad && config.followers().contains(&other)
+            });
+            if taken {
+                return Err(format!(
+                    "{other:?} is already following another pad"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The metric shown on each pad, bar or history and how, in order of
+    /// their first pad.
+    fn into_layout(mut self, display: &Style) -> Vec<Group> {
+        let followers: Vec<Pad> = self
+            .pads
+            .values()
+            .flat_map(|config| config.followers().iter().copied())
+            .collect();
+        PADS.iter()
+            .filter(|pad| !followers.contains(pad))
+            .map(|&pad| {
+                let Some(config) = self.pads.remove(&pad) else {
+                    let metric = default_metric(pad);
+                    let style =
+                        Style::default().or(display, metric.is_discrete());
+                    return Group {
+                        pads: vec![pad],
+                        metric,
+                        style,
+                        history: None,
+                        rotation: None,
+                        acknowledged: 0,
+                    };
+                };
+                let mut pads = vec![pad];
+                pads.extend(config.followers());
+                let history = (!config.history.is_empty()).then(|| {
+                    History::new(
+                        Duration::from_secs_f32(config.history_step),
+                        pads.len(),
+                    )
+                });
+                let pad_style = &config.style;
+                let dwell = Duration::from_secs_f32(config.dwell);
+                let others: Vec<(Metric, Style)> = config
+                    .metrics
+                    .into_iter()
+                    .map(|RotatedMetric(metric)| {
+                        let style = pad_style
+                            .clone()
+                            .or(display, metric.is_discrete());
+                        (metric, style)
+                    })
+                    .collect();
+                let rotation =
+                    (!others.is_empty()).then(|| Rotation::new(others, dwell));
+                let style =
+                    config.style.or(display, config.metric.is_discrete());
+                Group {
+                    pads,
+                    metric: config.metric,
+                    style,
+                    history,
+                    rotation,
+                    acknowledged: 0,
+                }
+            })
+            .collect()
+    }
+}
+
+fn default_metric(pad: Pad) -> Metric {
+    match pad {
+        Pad::Pad1 => Metric::CpuUsage,
+        Pad::Pad2 => Metric::CpuTemperature {
+            sensor: default_temperature_sensor(),
+            warning: WarnOnce::default(),
+        },
+        Pad::Pad3 => Metric::MemoryUsage {
+            mode: MemoryMode::default(),
+        },
+        Pad::Pad4 => Metric::Clock,
+        Pad::Pad5 => Metric::NetworkTransmitted {
+            interface: None,
+            warning: WarnOnce::default(),
+        },
+        Pad::Pad6 => Metric::NetworkReceived {
+            interface: None,
+            warning: WarnOnce::default(),
+        },
+        Pad::Pad7 => Metric::NetworkTransmittedErrors,
+        Pad::Pad8 => Metric::NetworkReceivedErrors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(table: &str) -> Result<PadConfig, String> {
+        toml::from_str(table).map_err(|err| err.to_string())
+    }
+
+    fn load(name: &str, contents: &str) -> Result<Config, String> {
+        let path = std::env::temp_dir()
+            .join(format!("lpd8-config-{}-{name}.toml", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        let config = Config::load(&path);
+        std::fs::remove_file(path).unwrap();
+        config
+    }
+
+    #[test]
+    fn pad_settings_of_every_kind_are_accepted() {
+        let config = parse(
+            "metric = \"http\"\nurl = \"http://localhost\"\nperiod = \
+             50\ncurve = 2.2\nbar = [2, 3]\nmetrics = [\"load_average\", { \
+             metric = \"memory_usage\" }]",
+        )
+        .unwrap();
+        assert_eq!(config.bar, [Pad::Pad2, Pad::Pad3]);
+        assert_eq!(config.metrics.len(), 2);
+        assert!(parse("metric = \"cpu_usage\"\nperiod = 50").is_ok());
+    }
+
+    #[test]
+    fn misspelled_pad_settings_are_rejected() {
+        for table in [
+            "metric = \"http\"\nurl = \"http://localhost\"\nperod = 50",
+            "metric = \"cpu_usage\"\ngama = 2.2",
+            "metric = \"cpu_usage\"\nmetrics = [{ metric = \"memory_usage\", \
+             mdoe = \"used\" }]",
+            "metric = \"cpu_usage\"\nmetrics = [{ metric = \"battery\", x = \
+             1 }]",
+        ] {
+            let err = parse(table).err().unwrap();
+            assert!(err.contains("unknown field"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn pages_have_to_agree_on_worker_settings() {
+        let pages = |first: &str, second: &str| {
+            format!(
+                "[[pages]]\n[pages.pads.1]\nmetric = \"cpu_usage\"\nbar = \
+                 [2]\n{first}\n[[pages]]\n[pages.pads.1]\nmetric = \
+                 \"memory_usage\"\n{first}\n[pages.pads.2]\nmetric = \
+                 \"load_average\"\n{second}"
+            )
+        };
+        assert!(load("same", &pages("period = 50", "period = 50")).is_ok());
+        assert!(load(
+            "display",
+            &format!("[display]\nphase = 0.5\n{}", pages("", ""))
+        )
+        .is_ok());
+        for (name, first, second) in [
+            ("period", "period = 50", ""),
+            ("quantize", "quantize = 4", "quantize = 8"),
+            ("dither", "", "dither = 0.1"),
+            ("seed", "dither = 0.1\ndither_seed = 1", "dither = 0.1"),
+            ("phase", "phase = 0.5", "phase = 0.25"),
+        ] {
+            let err = load(name, &pages(first, second)).err().unwrap();
+            assert!(err.contains("same on every page"), "{}", err);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Dimming every pad at once: while the machine is idle, at night, or
+//! when told to.
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::screensaver;
+use crate::utility::LocalTime;
+
+/// What every duty cycle is scaled by while nothing's forced, as the bits
+/// of an `f32`; starts at 1.
+static BRIGHTNESS: AtomicU32 = AtomicU32::new(0x3F80_0000);
+
+/// Whether the pads are forced bright or dark, whatever the idle
+/// detection says.
+static FORCED: AtomicU8 = AtomicU8::new(AUTOMATIC);
+
+const AUTOMATIC: u8 = 0;
+const BRIGHT: u8 = 1;
+const DARK: u8 = 2;
+
+#[derive(Clone, Copy)]
+pub enum Force {
+    Bright,
+    Dark,
+}
+
+/// Keeps the pads at full brightness or dark until told otherwise.
+pub fn force(force: Force) {
+    let forced = match force {
+        Force::Bright => BRIGHT,
+        Force::Dark => DARK,
+    };
+    FORCED.store(forced, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+extern "C" fn on_toggle(_signal: libc::c_int) {
+    let next = (FORCED.load(Ordering::Relaxed) + 1) % 3;
+    FORCED.store(next, Ordering::Relaxed);
+}
+
+/// Goes from automatic to forced bright, forced dark and back whenever the
+/// process gets `SIGUSR2`, e.g. from `pkill -USR2 lpd8_monitor`.
+#[cfg(unix)]
+pub fn listen_for_toggles() {
+    // SAFETY: the handler only touches an atomic, which is
+    // async-signal-safe.
+    unsafe {
+        libc::signal(
+            libc::SIGUSR2,
+            on_toggle as *const () as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(not(unix))]
+pub const fn listen_for_toggles() {}
+
+/// `duty_cycle`, dimmed however the pads are dimmed right now. Pads
+/// showing an alert can be let through at night.
+pub fn limit(duty_cycle: f32, alerting: bool) -> f32 {
+    match FORCED.load(Ordering::Relaxed) {
+        BRIGHT => return duty_cycle,
+        DARK => return 0.0,
+        _ => {}
+    }
+    let duty_cycle =
+        duty_cycle * f32::from_bits(BRIGHTNESS.load(Ordering::Relaxed));
+    let night = NIGHT_CAP.load(Ordering::Relaxed);
+    if night == NOT_NIGHT || alerting && NIGHT_ALERTS.load(Ordering::Relaxed) {
+        duty_cycle
+    } else {
+        duty_cycle.min(f32::from_bits(night))
+    }
+}
+
+fn set_brightness(brightness: f32) {
+    BRIGHTNESS.store(brightness.to_bits(), Ordering::Relaxed);
+}
+
+const fn default_below() -> f32 { 0.05 }
+
+const fn default_minutes() -> f32 { 10.0 }
+
+const fn default_dim() -> f32 { 0.2 }
+
+/// The `[idle]` table: dimming the pads once nothing much has been going
+/// on for a while.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Idle {
+    /// Value every pad has to stay below for the machine to count as idle.
+    #[serde(default = "default_below")]
+    below: f32,
+    /// Minutes the machine has to be idle for before the pads dim.
+    #[serde(default = "default_minutes")]
+    minutes: f32,
+    /// Brightness while idle; 0 blanks the pads.
+    #[serde(default = "default_dim")]
+    dim: f32,
+    /// Sweeps a light across the pads while idle, instead of dimming
+    /// them.
+    #[serde(default)]
+    screensaver: bool,
+    #[serde(skip)]
+    quiet_since: Option<Instant>,
+}
+
+impl Idle {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.below > 0.0 && self.below <= 1.0) {
+            Err("idle.below must be above 0 and at most 1".into())
+        } else if !(self.minutes >= 0.0 && self.minutes.is_finite()) {
+            Err("idle.minutes can't be negative".into())
+        } else if !(0.0..=1.0).contains(&self.dim) {
+            Err("idle.dim must be from 0 to 1".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Dims the pads, or starts the screensaver, once all of `levels` have
+    /// stayed below `below` for long enough, and goes back to normal as
+    /// soon as one hasn't.
+    pub fn update(&mut self, mut levels: impl Iterator<Item = f32>) {
+        if levels.any(|level| level >= self.below) {
+            self.quiet_since = None;
+            screensaver::stop();
+            set_brightness(1.0);
+            return;
+        }
+        let now = Instant::now();
+        let quiet_since = *self.quiet_since.get_or_insert(now);
+        let idle = Duration::from_secs_f32(self.minutes * 60.0);
+        if now.duration_since(quiet_since) < idle {
+            return;
+        }
+        if self.screensaver {
+            screensaver::start();
+        } else {
+            set_brightness(self.dim);
+        }
+    }
+}
+
+/// Highest duty cycle allowed right now, as the bits of an `f32`, or
+/// [`NOT_NIGHT`].
+static NIGHT_CAP: AtomicU32 = AtomicU32::new(NOT_NIGHT);
+
+/// Whether alerts show at full brightness anyway.
+static NIGHT_ALERTS: AtomicBool = AtomicBool::new(false);
+
+/// Not a valid `f32` cap: the bits of a NaN.
+const NOT_NIGHT: u32 = u32::MAX;
+
+/// A `[[night]]` table: a time of day during which the pads are kept dark,
+/// or at most at `cap`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Night {
+    /// Local time the window starts at, like `"23:00"`.
+    from: TimeOfDay,
+    /// Local time it ends at; before `from` for windows that go past
+    /// midnight.
+    to: TimeOfDay,
+    /// Highest duty cycle during the window.
+    #[serde(default)]
+    cap: f32,
+    /// Lets alerts show at full brightness anyway.
+    #[serde(default)]
+    alerts: bool,
+}
+
+impl Night {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.from == self.to {
+            Err("night can't start and end at the same time".into())
+        } else if !(0.0..=1.0).contains(&self.cap) {
+            Err("night.cap must be from 0 to 1".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn contains(&self, now: TimeOfDay) -> bool {
+        if self.from < self.to {
+            self.from <= now && now < self.to
+        } else {
+            now >= self.from || now < self.to
+        }
+    }
+
+    /// Caps the pads as long as the local time is in one of `windows`.
+    /// Checks the clock afresh every time, so a change of time zone or
+    /// daylight saving time shows right away.
+    pub fn update(windows: &[Self]) {
+        let active = TimeOfDay::now().and_then(|now| {
+            windows.iter().find(|window| window.contains(now))
+        });
+        match active {
+            Some(window) => {
+                NIGHT_ALERTS.store(window.alerts, Ordering::Relaxed);
+                NIGHT_CAP.store(window.cap.to_bits(), Ordering::Relaxed);
+            }
+            None => NIGHT_CAP.store(NOT_NIGHT, Ordering::Relaxed),
+        }
+    }
+}
+
+/// Minutes since local midnight.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+struct TimeOfDay(u32);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(time: String) -> Result<Self, String> {
+        time.split_once(':')
+            .and_then(|(hours, minutes)| {
+                let hours: u32 = hours.parse().ok()?;
+                let minutes: u32 = minutes.parse().ok()?;
+                (hours < 24 && minutes < 60)
+                    .then_some(Self(hours * 60 + minutes))
+            })
+            .ok_or_else(|| format!("{time:?} isn't a time like \"23:00\""))
+    }
+}
+
+impl TimeOfDay {
+    fn now() -> Option<Self> {
+        LocalTime::now().map(|now| Self(now.hour * 60 + now.minute))
+    }
+}
@@ -0,0 +1,142 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Jittering where PWM cycles start, so a fixed period doesn't beat
+//! against the LED driver or a camera's shutter.
+//!
+//! Each boundary between cycles is moved by its own random amount, either
+//! way, from where it'd be without dithering. Since the moves don't add
+//! up, the pad never drifts off its schedule, and since the on time is
+//! its share of each cycle as it ends up, the average duty cycle stays the
+//! same.
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Where the boundaries between one pad's cycles go.
+pub struct Dither {
+    /// Most a boundary moves, as a share of the period.
+    spread: f32,
+    /// State of the splitmix64 generator.
+    state: u64,
+}
+
+impl Dither {
+    /// Dithering that changes the length of a cycle by up to `amount` of
+    /// the period, either way. The same `seed` always gives the same
+    /// jitter; without one, it's seeded from the clock.
+    pub fn new(amount: f32, seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            now.as_secs() ^ u64::from(now.subsec_nanos()).rotate_left(32)
+        });
+        // Both ends of a cycle move, so each only goes half as far.
+        Self {
+            spread: amount / 2.0,
+            state: seed,
+        }
+    }
+
+    /// How far to move the next boundary, as a share of the period from
+    /// `-spread` to `spread`.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn next_offset(&mut self) -> f32 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        // The top 24 bits are as many as an f32 holds exactly.
+        let uniform = (z >> 40) as f32 / (1u64 << 24) as f32;
+        self.spread * uniform.mul_add(2.0, -1.0)
+    }
+}
+
+/// `instant` moved by `offset` of `period`, either way.
+pub fn shift(instant: Instant, period: Duration, offset: f32) -> Instant {
+    let by = period.mul_f32(offset.abs());
+    if offset < 0.0 {
+        instant.checked_sub(by).unwrap_or(instant)
+    } else {
+        instant + by
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(dither: &mut Dither, count: usize) -> Vec<f32> {
+        (0..count).map(|_| dither.next_offset()).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_jitter() {
+        let first = offsets(&mut Dither::new(0.2, Some(42)), 100);
+        let second = offsets(&mut Dither::new(0.2, Some(42)), 100);
+        assert_eq!(first, second);
+        let other = offsets(&mut Dither::new(0.2, Some(43)), 100);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn offsets_stay_within_the_spread() {
+        let mut dither = Dither::new(0.2, Some(7));
+        for offset in offsets(&mut dither, 100_000) {
+            assert!((-0.1..=0.1).contains(&offset), "{} is too far", offset);
+        }
+    }
+
+    #[test]
+    fn offsets_average_out() {
+        let count = 100_000;
+        let sum: f64 = offsets(&mut Dither::new(0.2, Some(7)), count)
+            .into_iter()
+            .map(f64::from)
+            .sum();
+        #[allow(clippy::cast_precision_loss)]
+        let mean = sum / count as f64;
+        assert!(mean.abs() < 0.002, "mean offset is {}", mean);
+    }
+
+    #[test]
+    fn cycles_dont_drift_off_schedule() {
+        // Steps through cycles the way the pad workers do.
+        let period = Duration::from_millis(20);
+        let epoch = Instant::now() + Duration::from_secs(1);
+        let mut dither = Dither::new(0.5, Some(1));
+        let mut start = epoch;
+        let mut offset = 0.0;
+        let mut lengths = Duration::ZERO;
+        let cycles = 10_000u32;
+        for _ in 0..cycles {
+            let next = dither.next_offset();
+            let from = shift(start, period, offset);
+            let to = shift(start + period, period, next);
+            lengths += to.saturating_duration_since(from);
+            offset = next;
+            start += period;
+        }
+        let end = shift(start, period, offset);
+        let scheduled = epoch + period * cycles;
+        let off = end.max(scheduled).duration_since(end.min(scheduled));
+        assert!(off <= period.mul_f32(0.25));
+        // The cycles add up to the time they cover, jitter and all.
+        let covered = end.saturating_duration_since(epoch);
+        let slack = lengths.abs_diff(covered);
+        assert!(slack < Duration::from_micros(1), "{:?} off", slack);
+    }
+
+    #[test]
+    fn shifting_moves_either_way() {
+        let now = Instant::now() + Duration::from_secs(1);
+        let period = Duration::from_millis(100);
+        let later = shift(now, period, 0.1).duration_since(now);
+        assert!(later.abs_diff(Duration::from_millis(10)).as_nanos() < 10);
+        let earlier = now.duration_since(shift(now, period, -0.1));
+        assert!(earlier.abs_diff(Duration::from_millis(10)).as_nanos() < 10);
+        assert_eq!(shift(now, period, 0.0), now);
+    }
+}
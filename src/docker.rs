@@ -0,0 +1,58 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Just enough of the Docker Engine API to count running containers.
+use std::io::{Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use serde::de::IgnoredAny;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of running containers, or `None` if the daemon couldn't be
+/// reached.
+///
+/// `host` is either a socket path, `unix://<path>` or `tcp://<host>:<port>`,
+/// the same as `DOCKER_HOST`. TLS isn't supported.
+pub fn running_containers(host: &str) -> Option<usize> {
+    let response = if let Some(address) = host.strip_prefix("tcp://") {
+        let stream = TcpStream::connect(address).ok()?;
+        stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+        request(stream)?
+    } else {
+        unix_request(host.strip_prefix("unix://").unwrap_or(host))?
+    };
+    let (head, body) = response.split_once("\r\n\r\n")?;
+    if !head.starts_with("HTTP/1.0 200") && !head.starts_with("HTTP/1.1 200") {
+        return None;
+    }
+    serde_json::from_str::<Vec<IgnoredAny>>(body)
+        .ok()
+        .map(|containers| containers.len())
+}
+
+#[cfg(unix)]
+fn unix_request(path: &str) -> Option<String> {
+    let stream = UnixStream::connect(path).ok()?;
+    stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+    request(stream)
+}
+
+#[cfg(not(unix))]
+const fn unix_request(_path: &str) -> Option<String> { None }
+
+fn request(mut stream: impl Read + Write) -> Option<String> {
+    // HTTP/1.0 keeps the daemon from chunking the body, and closes the
+    // connection once it's sent.
+    stream
+        .write_all(b"GET /containers/json HTTP/1.0\r\nHost: docker\r\n\r\n")
+        .ok()?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+    Some(response)
+}
@@ -0,0 +1,46 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+use std::path::Path;
+use std::time::Duration;
+
+/// How long ago the file at `path` was last modified. Timestamps in the
+/// future count as just now.
+pub fn age(path: &Path) -> Option<Duration> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.elapsed().unwrap_or_default())
+}
+
+/// Share of the inodes in use on the filesystem containing `path`.
+///
+/// Filesystems that allocate inodes dynamically, like btrfs, report a
+/// total of 0 and read as 0.
+#[cfg(unix)]
+#[allow(clippy::cast_precision_loss, clippy::useless_conversion)]
+pub fn inode_usage(path: &Path) -> Option<f32> {
+    use std::ffi::CString;
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated, and `stat` is only read once
+    // statvfs has filled it in.
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+    let total = u64::from(stat.f_files);
+    if total == 0 {
+        return Some(0.0);
+    }
+    let used = total.saturating_sub(u64::from(stat.f_ffree));
+    Some(used as f32 / total as f32)
+}
+
+#[cfg(not(unix))]
+pub const fn inode_usage(_path: &Path) -> Option<f32> { None }
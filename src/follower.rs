@@ -0,0 +1,139 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Counting events in logs, by following a command like `journalctl
+//! --follow` or `tail -F` that prints new lines as they arrive.
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+use crate::metric::WarnOnce;
+
+/// How long to wait before starting the command again after it exits.
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
+/// Keeps track of how many lines the command printed in the last `window`
+/// that are events.
+pub struct Follower {
+    rx: Receiver<Instant>,
+    window: Duration,
+    recent: VecDeque<Instant>,
+    /// Events seen since the command was first started.
+    total: u64,
+}
+
+impl Follower {
+    pub fn spawn(
+        window: Duration,
+        command: impl Fn() -> Command + Send + 'static,
+        is_event: impl Fn(&str) -> bool + Send + 'static,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        spawn(move || {
+            // Whatever makes the command exit, be it a crash or the log
+            // being rotated away, just start it over, until the metric is
+            // gone. A command that can't even start says so only once.
+            let mut warning = WarnOnce::default();
+            while follow(command(), &is_event, &tx, &mut warning) {
+                sleep(RESTART_DELAY);
+            }
+        });
+        Self {
+            rx,
+            window,
+            recent: VecDeque::new(),
+            total: 0,
+        }
+    }
+
+    /// Number of events in the last `window`.
+    pub fn count(&mut self) -> usize {
+        for arrived in self.rx.try_iter() {
+            self.recent.push_back(arrived);
+            self.total += 1;
+        }
+        let now = Instant::now();
+        while self
+            .recent
+            .front()
+            .is_some_and(|&arrived| now.duration_since(arrived) > self.window)
+        {
+            self.recent.pop_front();
+        }
+        self.recent.len()
+    }
+}
+
+impl Follower {
+    /// Events seen so far, as of the last [`Self::count`].
+    pub const fn total(&self) -> u64 { self.total }
+}
+
+/// Runs `command` until it exits, sending the arrival time of each event
+/// it prints. Returns false once nobody's listening anymore.
+fn follow(
+    mut command: Command,
+    is_event: impl Fn(&str) -> bool,
+    tx: &Sender<Instant>,
+    warning: &mut WarnOnce,
+) -> bool {
+    let child = command.stdout(Stdio::piped()).stderr(Stdio::null()).spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            warning.warn(format_args!(
+                "Couldn't run {}: {err}",
+                command.get_program().to_string_lossy()
+            ));
+            return true;
+        }
+    };
+    let mut listening = true;
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            if !is_event(&line) {
+                continue;
+            }
+            if tx.send(Instant::now()).is_err() {
+                listening = false;
+                break;
+            }
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    listening
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_command_keeps_retrying_and_warns_once() {
+        let (tx, _rx) = mpsc::channel();
+        let mut warning = WarnOnce::default();
+        for _ in 0..3 {
+            let command = Command::new("/nonexistent/journalctl");
+            assert!(follow(command, |_| true, &tx, &mut warning));
+            assert!(warning.warned());
+        }
+    }
+
+    #[test]
+    fn stops_once_nobody_listens() {
+        let (tx, rx) = mpsc::channel();
+        drop(rx);
+        let mut command = Command::new("echo");
+        command.arg("xrun");
+        let mut warning = WarnOnce::default();
+        assert!(!follow(command, |_| true, &tx, &mut warning));
+        assert!(!warning.warned());
+    }
+}
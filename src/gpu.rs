@@ -0,0 +1,120 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! NVIDIA GPU metrics, read through NVML, and AMD video memory from sysfs.
+//!
+//! NVML is loaded at runtime, so a machine without the NVIDIA driver just
+//! gets dark GPU pads instead of a binary that refuses to start.
+
+#[cfg(feature = "gpu")]
+mod nvml {
+    use std::sync::OnceLock;
+
+    use nvml_wrapper::Nvml;
+
+    static NVML: OnceLock<Option<Nvml>> = OnceLock::new();
+
+    /// The process-wide NVML handle, initialized on first use.
+    pub fn handle() -> Option<&'static Nvml> {
+        NVML.get_or_init(|| {
+            Nvml::init()
+                .map_err(|err| {
+                    eprintln!(
+                        "NVML is unavailable, GPU pads stay dark: {err}"
+                    );
+                })
+                .ok()
+        })
+        .as_ref()
+    }
+}
+
+/// Utilization of the GPU with the given index.
+#[cfg(feature = "gpu")]
+pub fn usage_percent(device: u32) -> Option<f32> {
+    let rates = nvml::handle()?
+        .device_by_index(device)
+        .ok()?
+        .utilization_rates()
+        .ok()?;
+    #[allow(clippy::cast_precision_loss)]
+    Some(rates.gpu as f32 / 100.0)
+}
+
+/// Core temperature of the GPU with the given index, in °C.
+#[cfg(feature = "gpu")]
+pub fn temperature(device: u32) -> Option<f32> {
+    use nvml_wrapper::enum_wrappers::device::TemperatureSensor;
+
+    let temperature = nvml::handle()?
+        .device_by_index(device)
+        .ok()?
+        .temperature(TemperatureSensor::Gpu)
+        .ok()?;
+    #[allow(clippy::cast_precision_loss)]
+    Some(temperature as f32)
+}
+
+/// Share of the video memory of the GPU with the given index that's in use.
+///
+/// NVIDIA cards are asked through NVML; AMD cards are numbered in the order
+/// the kernel lists them.
+pub fn memory_percent(device: u32) -> Option<f32> {
+    nvml_memory_percent(device).or_else(|| amdgpu_memory_percent(device))
+}
+
+#[cfg(feature = "gpu")]
+fn nvml_memory_percent(device: u32) -> Option<f32> {
+    let memory = nvml::handle()?
+        .device_by_index(device)
+        .ok()?
+        .memory_info()
+        .ok()?;
+    (memory.total > 0).then(|| {
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = memory.used as f32 / memory.total as f32;
+        fraction
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn amdgpu_memory_percent(device: u32) -> Option<f32> {
+    use std::convert::TryFrom;
+    use std::fs::{read_dir, read_to_string};
+
+    let read = |card: &std::path::Path, attribute: &str| {
+        read_to_string(card.join("device").join(attribute))
+            .ok()?
+            .trim()
+            .parse::<u64>()
+            .ok()
+    };
+    // Connectors show up as card0-DP-1 and the like; only the cards
+    // themselves have a VRAM size.
+    let mut cards: Vec<_> = read_dir("/sys/class/drm")
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|card| read(card, "mem_info_vram_total").is_some())
+        .collect();
+    cards.sort();
+    let card = cards.get(usize::try_from(device).ok()?)?;
+    let total = read(card, "mem_info_vram_total")?;
+    let used = read(card, "mem_info_vram_used")?;
+    #[allow(clippy::cast_precision_loss)]
+    (total > 0).then(|| used as f32 / total as f32)
+}
+
+#[cfg(not(feature = "gpu"))]
+const fn nvml_memory_percent(_device: u32) -> Option<f32> { None }
+
+#[cfg(not(target_os = "linux"))]
+const fn amdgpu_memory_percent(_device: u32) -> Option<f32> { None }
+
+#[cfg(not(feature = "gpu"))]
+pub const fn usage_percent(_device: u32) -> Option<f32> { None }
+
+#[cfg(not(feature = "gpu"))]
+pub const fn temperature(_device: u32) -> Option<f32> { None }
@@ -0,0 +1,56 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Showing a metric's recent past across a row of pads.
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::render::Show;
+
+/// What a pad showed over the last few steps, newest first.
+pub struct History {
+    step: Duration,
+    length: usize,
+    shows: VecDeque<Show>,
+    shifted: Instant,
+}
+
+impl History {
+    /// Keeps `length` shows, one for every `step`.
+    pub fn new(step: Duration, length: usize) -> Self {
+        Self {
+            step,
+            length,
+            shows: VecDeque::with_capacity(length),
+            shifted: Instant::now(),
+        }
+    }
+
+    /// Takes the latest show, and returns what each pad shows: `show`
+    /// itself first, then what was showing a step ago, and so on. Pads
+    /// further back than the history goes yet stay dark.
+    ///
+    /// Everything moves along by one pad at the same moment, so a spike
+    /// travels across the row in one piece.
+    pub fn record(&mut self, show: Show) -> Vec<Show> {
+        let now = Instant::now();
+        if self.shows.is_empty() || now >= self.shifted + self.step {
+            self.shows.push_front(show);
+            self.shows.truncate(self.length);
+            // Kept on the step's own schedule, however late the sampler
+            // gets round to it.
+            while self.shifted + self.step <= now {
+                self.shifted += self.step;
+            }
+        } else {
+            self.shows[0] = show;
+        }
+        (0..self.length)
+            .map(|index| {
+                self.shows.get(index).cloned().unwrap_or(Show::Level(0.0))
+            })
+            .collect()
+    }
+}
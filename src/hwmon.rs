@@ -0,0 +1,51 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Sensors from `/sys/class/hwmon` that `sysinfo` doesn't pick up.
+
+/// A fan's current speed, and its maximum if the driver reports one, in
+/// RPM.
+pub struct Fan {
+    pub rpm: u64,
+    pub max: Option<u64>,
+}
+
+#[cfg(target_os = "linux")]
+pub fn fans() -> Vec<Fan> {
+    use std::fs::{read_dir, read_to_string};
+    use std::path::Path;
+
+    fn read_u64(path: &Path) -> Option<u64> {
+        read_to_string(path).ok()?.trim().parse().ok()
+    }
+
+    let mut fans = vec![];
+    let Ok(chips) = read_dir("/sys/class/hwmon") else {
+        return fans;
+    };
+    for chip in chips.filter_map(Result::ok).map(|entry| entry.path()) {
+        let Ok(inputs) = read_dir(&chip) else {
+            continue;
+        };
+        for input in inputs.filter_map(Result::ok) {
+            let name = input.file_name();
+            let name = name.to_string_lossy();
+            if !(name.starts_with("fan") && name.ends_with("_input")) {
+                continue;
+            }
+            if let Some(rpm) = read_u64(&input.path()) {
+                let max = name.replace("_input", "_max");
+                fans.push(Fan {
+                    rpm,
+                    max: read_u64(&chip.join(max)).filter(|&max| max > 0),
+                });
+            }
+        }
+    }
+    fans
+}
+
+#[cfg(not(target_os = "linux"))]
+pub const fn fans() -> Vec<Fan> { Vec::new() }
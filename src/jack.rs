@@ -0,0 +1,30 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Xruns of the JACK audio server, read from its log.
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where `jackdbus` logs to. That's the JACK server that session managers
+/// like Cadence start.
+pub fn default_log() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".log/jack/jackdbus.log"))
+}
+
+/// A `tail` printing each new line of `log`.
+///
+/// `tail -F` keeps retrying while the log doesn't exist and picks it up
+/// again when it's rotated, so JACK can come and go as it likes.
+pub fn follow_log(log: &Path) -> Command {
+    let mut command = Command::new("tail");
+    command.args(["-F", "-n", "0"]).arg(log);
+    command
+}
+
+/// Whether `line` is JACK reporting an xrun. Depending on the backend and
+/// version, that's `xrun`, `XRUN` or `Xrun`.
+pub fn is_xrun(line: &str) -> bool { line.to_lowercase().contains("xrun") }
@@ -0,0 +1,130 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+use midir::{MidiOutputConnection, SendError};
+
+use crate::utility::{all_notes_off, note_off, note_on, Pad, PADS};
+
+/// Where the LED's MIDI messages go: the LPD8, or a stand-in.
+pub trait Output {
+    fn send(&mut self, message: &[u8]) -> Result<(), SendError>;
+}
+
+impl Output for MidiOutputConnection {
+    fn send(&mut self, message: &[u8]) -> Result<(), SendError> {
+        Self::send(self, message)
+    }
+}
+
+/// The LED of one pad. Only sends a message when the LED actually has to
+/// change, so a pad that stays dark or fully lit stays quiet.
+pub struct Led<O: Output = MidiOutputConnection> {
+    connection: O,
+    pad: Pad,
+    /// What the LED was last switched to; `None` until the first message,
+    /// since the pad could be in either state when we connect.
+    lit: Option<bool>,
+}
+
+impl<O: Output> Led<O> {
+    pub const fn new(connection: O, pad: Pad) -> Self {
+        Self {
+            connection,
+            pad,
+            lit: None,
+        }
+    }
+
+    /// Has the next `set` send its message, whatever the LED was last
+    /// switched to.
+    pub const fn forget(&mut self) { self.lit = None; }
+
+    pub fn set(&mut self, lit: bool) -> Result<(), SendError> {
+        if self.lit == Some(lit) {
+            return Ok(());
+        }
+        let message = if lit {
+            note_on(self.pad)
+        } else {
+            note_off(self.pad)
+        };
+        self.connection.send(&message)?;
+        self.lit = Some(lit);
+        Ok(())
+    }
+}
+
+impl<O: Output> Drop for Led<O> {
+    /// Leaves every pad dark, not just this one: whichever worker goes
+    /// last, nothing another one lit is left behind. "All notes off" goes
+    /// out as well, for good measure.
+    fn drop(&mut self) {
+        for pad in PADS {
+            let _ = self.connection.send(&note_off(pad));
+        }
+        let _ = self.connection.send(&all_notes_off());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    /// Keeps every message instead of sending it.
+    impl Output for &mut Vec<[u8; 3]> {
+        fn send(&mut self, message: &[u8]) -> Result<(), SendError> {
+            self.push(<[u8; 3]>::try_from(message).unwrap());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sends_only_changes() {
+        let mut sent = vec![];
+        let mut led = Led::new(&mut sent, Pad::Pad3);
+        for lit in [true, true, false, false, false, true] {
+            led.set(lit).unwrap();
+        }
+        std::mem::forget(led);
+        assert_eq!(
+            sent,
+            [note_on(Pad::Pad3), note_off(Pad::Pad3), note_on(Pad::Pad3)]
+        );
+    }
+
+    #[test]
+    fn first_message_always_goes_out() {
+        let mut sent = vec![];
+        let mut led = Led::new(&mut sent, Pad::Pad1);
+        led.set(false).unwrap();
+        std::mem::forget(led);
+        assert_eq!(sent, [note_off(Pad::Pad1)]);
+    }
+
+    #[test]
+    fn forgetting_sends_the_next_message_again() {
+        let mut sent = vec![];
+        let mut led = Led::new(&mut sent, Pad::Pad2);
+        led.set(true).unwrap();
+        led.forget();
+        led.set(true).unwrap();
+        std::mem::forget(led);
+        assert_eq!(sent, [note_on(Pad::Pad2), note_on(Pad::Pad2)]);
+    }
+
+    #[test]
+    fn dropping_switches_every_pad_off() {
+        let mut sent = vec![];
+        let mut led = Led::new(&mut sent, Pad::Pad5);
+        led.set(true).unwrap();
+        drop(led);
+        let mut expected = vec![note_on(Pad::Pad5)];
+        expected.extend(PADS.iter().map(|&pad| note_off(pad)));
+        expected.push(all_notes_off());
+        assert_eq!(sent, expected);
+    }
+}
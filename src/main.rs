@@ -5,33 +5,138 @@
 // Copyright 2021, Slavfox.
 #![warn(clippy::pedantic, clippy::nursery)]
 
+mod acknowledge;
+mod cgroup;
+mod color;
+mod command;
+mod config;
+mod dimming;
+mod dither;
+mod docker;
+mod filesystem;
+mod follower;
+mod gpu;
+mod history;
+mod hwmon;
+mod jack;
+mod led;
+mod metric;
+mod morse;
+mod nut;
+mod paging;
+mod poller;
+mod power;
+mod probe;
+mod procfs;
+mod rapl;
+mod render;
+mod rotation;
+mod sampler;
+mod screensaver;
+mod shutdown;
+mod smart;
+mod splash;
+mod style;
+mod systemd;
+mod timer;
+mod timesync;
+mod updates;
 mod utility;
+mod vpn;
+mod watchdog;
 
-use std::sync::mpsc;
+use std::env;
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::thread::{sleep, spawn, JoinHandle};
-use std::time::Duration;
-
-use midir::{ConnectError, MidiOutput, MidiOutputConnection};
-use sysinfo::{RefreshKind, System, SystemExt};
-
-use utility::{note_off, note_on, Pad, Resources};
-
-macro_rules! pad_worker {
-    ($threads:expr, $src:ident, $pad:expr) => {
-        $threads.push(spawn(move || {
-            let mut connection = connect(stringify!($src)).unwrap();
-            let mut duty_cycle = 0f32;
-            loop {
-                match $src.try_recv() {
-                    Ok(val) => {
-                        duty_cycle = val;
-                    }
-                    Err(_) => {}
-                }
-                pwm(&mut connection, duty_cycle, $pad);
+use std::time::{Duration, Instant};
+
+use midir::{ConnectError, MidiOutput, MidiOutputConnection, SendError};
+
+use config::{Config, Group};
+use dimming::Force;
+use dither::Dither;
+use led::Led;
+use metric::Metric;
+use paging::Paging;
+use render::{Limiter, Renderer, Show};
+use sampler::Sampler;
+use style::Style;
+use utility::{Pad, PADS};
+
+/// Shows whatever `src` says on `pad`, with PWM cycles as `style` says
+/// lined up on `start` and no faster flashing than `max_blink_hz`, until a
+/// shutdown is requested. Returns false if it had
+/// to stop because of a MIDI error, having requested a shutdown itself so
+/// the other workers clean up too.
+fn pad_worker(
+    pad: Pad,
+    mut start: Instant,
+    style: &Style,
+    max_blink_hz: f32,
+    src: Receiver<Show>,
+) -> JoinHandle<bool> {
+    let period = style.period();
+    let mut limiter = Limiter::new(max_blink_hz, period);
+    let levels = style.levels();
+    let mut dither = style.dither(pad);
+    spawn(move || {
+        let connection = match connect(&format!("{pad:?}")) {
+            Ok(connection) => connection,
+            Err(err) => {
+                eprintln!("{pad:?} couldn't connect to the LPD8: {err}");
+                shutdown::request();
+                return false;
             }
-        }))
-    };
+        };
+        // Dropping the LED switches the pads off, however the loop ends.
+        let mut led = Led::new(connection, pad);
+        let mut renderer = Renderer::new();
+        // How far the start of the coming cycle is moved by dithering.
+        let mut offset = 0.0;
+        let mut switches = paging::switches();
+        while !shutdown::requested() {
+            // Nothing from the last page carries over to the next, and the
+            // pad is sent a note off or on however it was left.
+            if paging::switches() != switches {
+                switches = paging::switches();
+                renderer = Renderer::new();
+                led.forget();
+            }
+            catch_up(&mut renderer, &src);
+            // Worked out even while the screensaver shows, so animations
+            // carry on where they are once it stops.
+            let own = renderer.duty_cycle();
+            let duty_cycle = dimming::limit(
+                screensaver::duty_cycle(pad).unwrap_or(own),
+                renderer.alerting(),
+            );
+            let duty_cycle = levels.map_or(duty_cycle, |levels| {
+                render::quantize(duty_cycle, levels)
+            });
+            let next = dither.as_mut().map_or(0.0, Dither::next_offset);
+            let from = dither::shift(start, period, offset);
+            let to = dither::shift(start + period, period, next);
+            offset = next;
+            let duty_cycle = limiter.limit(duty_cycle, from);
+            let length = to.saturating_duration_since(from);
+            if let Err(err) = pwm(&mut led, duty_cycle, from, length) {
+                eprintln!("{pad:?} lost the LPD8: {err}");
+                shutdown::request();
+                return false;
+            }
+            start += period;
+            watchdog::cycled(pad);
+            // Skips the cycles missed while connecting or when the
+            // machine is too busy, rather than rushing through them.
+            let now = Instant::now();
+            while start + period < now {
+                start += period;
+            }
+        }
+        true
+    })
 }
 
 fn connect(
@@ -42,11 +147,12 @@ fn connect(
     midi_out
         .ports()
         .iter()
-        .find(|&port| match midi_out.port_name(port) {
-            Ok(name) => name.contains("LPD8"),
-            Err(_) => false,
+        .find(|&port| {
+            midi_out
+                .port_name(port)
+                .is_ok_and(|name| name.contains("LPD8"))
         })
-        .and_then(|port| {
+        .map(|port| {
             println!(
                 "Connecting to port {}.",
                 midi_out.port_name(port).expect(
@@ -54,85 +160,306 @@ fn connect(
                      established."
                 )
             );
-            Some(midi_out.connect(port, client_name))
+            midi_out.connect(port, client_name)
         })
         .expect("Could not connect to LPD8")
 }
 
-fn pwm(conn: &mut MidiOutputConnection, duty_cycle: f32, pad: Pad) {
+/// Hands `renderer` everything the sampler sent since the last cycle, so
+/// a pad with a long period doesn't fall further and further behind.
+/// Levels just replace each other, while pulses add up.
+fn catch_up(renderer: &mut Renderer, src: &Receiver<Show>) {
+    for show in src.try_iter() {
+        renderer.set(show);
+    }
+}
+
+/// Runs the PWM cycle starting at `start`. Returns once the pad's been
+/// switched off for the rest of it.
+fn pwm(
+    led: &mut Led,
+    duty_cycle: f32,
+    start: Instant,
+    period: Duration,
+) -> Result<(), SendError> {
+    sleep_until(start);
     if duty_cycle > 0.0 {
-        conn.send(&note_on(&pad)).unwrap();
+        led.set(true)?;
     }
-    let on_time = (DURATION as f32 * duty_cycle * duty_cycle) as u64;
-    sleep(Duration::from_millis(on_time));
+    sleep_until(start + on_time(duty_cycle, period));
     if duty_cycle < 1.0 {
-        conn.send(&note_off(&pad)).unwrap();
-        sleep(Duration::from_millis(DURATION - on_time));
+        led.set(false)?;
     }
+    Ok(())
 }
-const DURATION: u64 = 20;
-const REFRESH_INTERVAL: u64 = 100;
 
-fn main() {
-    let (cpu_usage_tx, cpu_usage_rx) = mpsc::channel();
-    let (cpu_temp_tx, cpu_temp_rx) = mpsc::channel();
-    let (memory_usage_tx, memory_usage_rx) = mpsc::channel();
-    let (network_sent_tx, network_sent_rx) = mpsc::channel();
-    let (network_received_tx, network_received_rx) = mpsc::channel();
-    let (network_sent_errors_tx, network_sent_errors_rx) = mpsc::channel();
-    let (network_received_errors_tx, network_received_errors_rx) =
-        mpsc::channel();
-    let (clock_tx, clock_rx) = mpsc::channel();
-
-    let mut threads: Vec<JoinHandle<()>> = vec![];
-    pad_worker!(threads, cpu_usage_rx, Pad::Pad1);
-    pad_worker!(threads, cpu_temp_rx, Pad::Pad2);
-    pad_worker!(threads, memory_usage_rx, Pad::Pad3);
-    pad_worker!(threads, clock_rx, Pad::Pad4);
-    pad_worker!(threads, network_sent_rx, Pad::Pad5);
-    pad_worker!(threads, network_received_rx, Pad::Pad6);
-    pad_worker!(threads, network_sent_errors_rx, Pad::Pad7);
-    pad_worker!(threads, network_received_errors_rx, Pad::Pad8);
-    threads.push(spawn(move || loop {
-        clock_tx.send(1.0).unwrap();
-        clock_tx.send(0.0).unwrap();
+/// How long the pad stays lit in a cycle of `period`. Kept to the
+/// nanosecond rather than the millisecond, so low duty cycles on short
+/// periods still get their sliver of on time.
+fn on_time(duty_cycle: f32, period: Duration) -> Duration {
+    period.mul_f32(duty_cycle.clamp(0.0, 1.0))
+}
+
+/// A worker for every pad, set up as `layout` says, and the channels to
+/// them, in pad order.
+fn spawn_workers(
+    layout: &[Group],
+    max_blink_hz: f32,
+) -> (Vec<JoinHandle<bool>>, Vec<Sender<Show>>) {
+    // All pads count their cycles from the same moment, so their phase
+    // offsets hold.
+    let epoch = Instant::now();
+    PADS.iter()
+        .filter_map(|&pad| {
+            let group =
+                layout.iter().find(|group| group.pads.contains(&pad))?;
+            let (tx, rx) = mpsc::channel();
+            let start = epoch + group.style.phase_offset(pad);
+            let worker =
+                pad_worker(pad, start, &group.style, max_blink_hz, rx);
+            Some((worker, tx))
+        })
+        .unzip()
+}
+
+/// The pads the heartbeat keeps an eye on, with their workers' periods:
+/// all of them but its own.
+fn watched(pages: &[Vec<Group>]) -> Vec<(Pad, Duration)> {
+    PADS.iter()
+        .copied()
+        .filter(|pad| {
+            !pages.iter().flatten().any(|group| {
+                matches!(group.metric, Metric::Heartbeat)
+                    && group.pads.contains(pad)
+            })
+        })
+        .filter_map(|pad| {
+            // The worker's period, which comes from the first page.
+            let group =
+                pages[0].iter().find(|group| group.pads.contains(&pad))?;
+            Some((pad, group.style.period()))
+        })
+        .collect()
+}
+
+/// How long a clock blinks for, the same as a heartbeat's beats.
+const CLOCK_BLINK: Duration = Duration::from_millis(100);
+
+/// Blinks the pads of a clock once per second while its `page` is up. Left
+/// running when shutting down, like the sampler: nothing is lost once the
+/// workers stop listening.
+///
+/// Each blink is a pulse, so the worker shows it however long its PWM
+/// period, and whatever else reached it in the same cycle.
+fn spawn_clock(txs: Vec<Sender<Show>>, page: usize) {
+    let blink = Show::Pulse {
+        events: 1,
+        length: CLOCK_BLINK,
+    };
+    spawn(move || loop {
+        let sent = !paging::showing(page)
+            || txs.iter().all(|tx| tx.send(blink.clone()).is_ok());
+        if !sent {
+            break;
+        }
         sleep(Duration::from_secs(1));
-    }));
-    threads.push(spawn(move || {
-        let mut system = System::new_with_specifics(
-            RefreshKind::everything()
-                .without_disks()
-                .without_disks_list()
-                .without_processes()
-                .without_users_list(),
-        );
-        loop {
-            system.refresh_all();
-            cpu_usage_tx
-                .send(system.get_cpu_usage_percent() / 100.0)
-                .unwrap();
-            cpu_temp_tx
-                .send(system.get_cpu_temperature_percent())
-                .unwrap();
-            memory_usage_tx
-                .send(system.get_memory_usage_percent())
-                .unwrap();
-            network_sent_tx
-                .send(system.get_network_transmitted_percent())
-                .unwrap();
-            network_received_tx
-                .send(system.get_network_received_percent())
-                .unwrap();
-            network_sent_errors_tx
-                .send(system.get_network_transmitted_error_percent())
-                .unwrap();
-            network_received_errors_tx
-                .send(system.get_network_received_error_percent())
-                .unwrap();
-            sleep(Duration::from_millis(REFRESH_INTERVAL));
+    });
+}
+
+fn sleep_until(deadline: Instant) {
+    if let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        sleep(left);
+    }
+}
+
+/// The page given with `--page`, counting from 1, if any. Exits if it's not
+/// one of the `pages`.
+fn page_arg(pages: usize) -> Option<usize> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--page" {
+            let page = args
+                .next()
+                .and_then(|page| page.parse().ok())
+                .filter(|page| (1..=pages).contains(page));
+            if page.is_none() {
+                eprintln!("--page takes a page from 1 to {pages}");
+                exit(1);
+            }
+            return page;
+        }
+    }
+    None
+}
+
+/// The config file given with `--config`, or the one in the user's config
+/// directory if it exists.
+fn config_path() -> Option<PathBuf> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--config" || arg == "-c" {
+            return args.next().map(PathBuf::from);
+        }
+    }
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+        })
+        .map(|dir| dir.join("lpd8_monitor.toml"))
+        .filter(|path| path.exists())
+}
+
+fn main() {
+    let mut config = config_path().map_or_else(Config::default, |path| {
+        Config::load(&path).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            exit(1);
+        })
+    });
+    shutdown::listen();
+    dimming::listen_for_toggles();
+    if env::args().any(|arg| arg == "--bright") {
+        dimming::force(Force::Bright);
+    } else if env::args().any(|arg| arg == "--dark") {
+        dimming::force(Force::Dark);
+    }
+    if !env::args().any(|arg| arg == "--no-splash") {
+        // On a connection of its own, since each pad worker makes its own.
+        let mut connection = connect("Splash").unwrap_or_else(|err| {
+            eprintln!("Couldn't connect to the LPD8: {err}");
+            exit(1);
+        });
+        let finished = splash::play(&mut connection);
+        if !finished {
+            exit(130);
         }
-    }));
-    for thread in threads {
-        thread.join().unwrap();
+    }
+    let device = config.device;
+    let max_blink_hz = config.max_blink_hz;
+    let paging = Paging::new(
+        config.pages(),
+        Duration::from_secs_f32(config.page_seconds),
+    );
+    if config.pages() > 1 {
+        paging::listen_for_selections(config.pages());
+    }
+    if let Some(page) = page_arg(config.pages()) {
+        paging::select(page);
+    }
+    let idle = config.idle.take();
+    let night = std::mem::take(&mut config.night);
+    let pages = config.into_pages();
+    if pages
+        .iter()
+        .flatten()
+        .flat_map(Group::metrics)
+        .any(|metric| matches!(metric, Metric::Timer { .. }))
+    {
+        timer::listen_for_resets();
+    }
+    // Kept until the end, since dropping it stops the listening.
+    let _presses = if pages.iter().flatten().any(|group| group.style.latches())
+    {
+        acknowledge::listen_for_hangups();
+        acknowledge::listen_for_presses()
+    } else {
+        None
+    };
+    let watched = watched(&pages);
+    let (workers, senders) = spawn_workers(&pages[0], max_blink_hz);
+    let mut by_page = vec![];
+    for (page, layout) in pages.into_iter().enumerate() {
+        let mut groups: Vec<(Vec<Sender<Show>>, Group)> = vec![];
+        for group in layout {
+            let txs = group
+                .pads
+                .iter()
+                .filter_map(|pad| PADS.iter().position(|other| other == pad))
+                .map(|index| senders[index].clone())
+                .collect();
+            match group.metric {
+                Metric::Clock => spawn_clock(txs, page),
+                Metric::Heartbeat => {
+                    watchdog::spawn_heartbeat(txs, &watched, page);
+                }
+                _ => groups.push((txs, group)),
+            }
+        }
+        by_page.push(groups);
+    }
+    let sampler = Sampler::new(by_page, senders, paging, device, idle, night);
+    spawn(move || sampler.run());
+    let mut failed = false;
+    for worker in workers {
+        // A worker that panicked still dropped its LED on the way out,
+        // but the others have to be told to stop.
+        if !worker.join().unwrap_or(false) {
+            failed = true;
+            shutdown::request();
+        }
+    }
+    if failed {
+        exit(1);
+    }
+    // The workers only stop cleanly when a signal asked them to.
+    exit(130);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catching_up_shows_the_latest_level() {
+        let (tx, rx) = mpsc::channel();
+        // A pad with a 10 s period hears from the sampler a hundred times
+        // a cycle.
+        for step in 0..=100u8 {
+            tx.send(Show::Level(f32::from(step) / 100.0)).unwrap();
+        }
+        let mut renderer = Renderer::new();
+        catch_up(&mut renderer, &rx);
+        assert!((renderer.duty_cycle() - 1.0).abs() < f32::EPSILON);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn catching_up_keeps_pulses() {
+        let (tx, rx) = mpsc::channel();
+        let pulse = Show::Pulse {
+            events: 1,
+            length: CLOCK_BLINK,
+        };
+        tx.send(pulse).unwrap();
+        tx.send(Show::Pulse {
+            events: 0,
+            length: CLOCK_BLINK,
+        })
+        .unwrap();
+        let mut renderer = Renderer::new();
+        catch_up(&mut renderer, &rx);
+        assert!((renderer.duty_cycle() - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn periods_come_from_the_style() {
+        let period = |config| toml::from_str::<Style>(config).unwrap();
+        assert_eq!(period("period = 1.0").period(), Duration::from_millis(1));
+        assert_eq!(
+            period("period = 10000.0").period(),
+            Duration::from_secs(10)
+        );
+        assert!(period("period = 0.5").validate().is_err());
+        assert!(period("period = 10001.0").validate().is_err());
+    }
+
+    #[test]
+    fn short_on_times_are_kept() {
+        let period = Duration::from_millis(1);
+        assert_eq!(on_time(0.05, period), Duration::from_micros(50));
+        assert!(on_time(0.001, period) > Duration::ZERO);
+        assert_eq!(on_time(0.0, period), Duration::ZERO);
+        assert_eq!(on_time(1.5, period), period);
     }
 }
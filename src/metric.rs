@@ -0,0 +1,2213 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+use std::fmt::Display;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use sysinfo::{ComponentExt, ProcessExt, RefreshKind, System, SystemExt};
+
+use crate::follower::Follower;
+use crate::nut::UpsState;
+use crate::poller::Poller;
+use crate::procfs::{CpuTimes, TcpState};
+use crate::utility::{Resources, UptimeStep};
+use crate::{
+    cgroup, command, docker, filesystem, gpu, hwmon, jack, nut, power, probe,
+    procfs, rapl, smart, systemd, timer, timesync, updates, utility, vpn,
+};
+
+/// Turns a monotonically increasing counter into a per-second rate.
+#[derive(Clone, Copy, Default)]
+pub struct Counter {
+    previous: Option<(u64, Instant)>,
+}
+
+impl Counter {
+    /// Returns how fast the counter grew since the last call.
+    ///
+    /// The first call, and any call where the counter went backwards
+    /// (because it wrapped or was reset), returns 0 instead of a bogus
+    /// spike.
+    pub fn rate(&mut self, current: u64) -> f32 {
+        self.advance(current, |current, previous| {
+            current.checked_sub(previous)
+        })
+    }
+
+    /// Like [`Self::rate`], for a counter that wraps back to 0 once it
+    /// reaches `range`.
+    pub fn wrapping_rate(&mut self, current: u64, range: u64) -> f32 {
+        self.advance(current, |current, previous| {
+            current.checked_sub(previous).or_else(|| {
+                range.checked_sub(previous).map(|rest| rest + current)
+            })
+        })
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn advance(
+        &mut self,
+        current: u64,
+        delta: impl FnOnce(u64, u64) -> Option<u64>,
+    ) -> f32 {
+        let now = Instant::now();
+        let rate = self
+            .previous
+            .and_then(|(previous, then)| {
+                let elapsed = now.duration_since(then).as_secs_f32();
+                let delta = delta(current, previous)?;
+                (elapsed > 0.0).then(|| delta as f32 / elapsed)
+            })
+            .unwrap_or(0.0);
+        self.previous = Some((current, now));
+        rate
+    }
+}
+
+/// Turns an event counter into a level: full brightness for a while after
+/// the counter goes up, then fading back to dark.
+#[derive(Clone, Copy, Default)]
+pub struct Latch {
+    previous: Option<u64>,
+    triggered: Option<Instant>,
+    /// Events counted since the baseline.
+    events: u64,
+}
+
+/// How long a latched pad takes to fade out once its hold time is over.
+const LATCH_FADE: Duration = Duration::from_secs(1);
+
+impl Latch {
+    /// Feeds the counter's current value, or `None` if it can't be read,
+    /// and returns the pad's level after holding for `hold`.
+    ///
+    /// The first reading only sets the baseline, so events from before the
+    /// monitor started don't light the pad.
+    pub fn update(&mut self, current: Option<u64>, hold: Duration) -> f32 {
+        if let Some(current) = current {
+            if let Some(previous) = self.previous.filter(|&p| current > p) {
+                self.triggered = Some(Instant::now());
+                self.events += current - previous;
+            }
+            self.previous = Some(current);
+        }
+        let Some(elapsed) = self.triggered.map(|then| then.elapsed()) else {
+            return 0.0;
+        };
+        let fading = elapsed.saturating_sub(hold);
+        (1.0 - fading.as_secs_f32() / LATCH_FADE.as_secs_f32()).max(0.0)
+    }
+
+    pub const fn events(&self) -> u64 { self.events }
+}
+
+const DISK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How long a disk check may take before the pad gives up on it.
+const DISK_POLL_TIMEOUT: Duration = Duration::from_secs(5);
+
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+const PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+const MDSTAT_INTERVAL: Duration = Duration::from_secs(5);
+
+const VPN_INTERVAL: Duration = Duration::from_secs(2);
+
+const JOURNAL_WINDOW: Duration = Duration::from_mins(1);
+
+const XRUN_WINDOW: Duration = Duration::from_mins(1);
+
+const TLS_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Refreshing the package databases can take a while on a slow mirror.
+const UPDATES_TIMEOUT: Duration = Duration::from_mins(5);
+
+const CLOCK_OFFSET_INTERVAL: Duration = Duration::from_mins(1);
+const CLOCK_OFFSET_TIMEOUT: Duration = Duration::from_secs(5);
+
+const NUT_TIMEOUT: Duration = Duration::from_secs(3);
+
+const DOCKER_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Since Linux 5.18 the entropy pool is 256 bits and always reads as full,
+/// so it's shown as a steady glow instead.
+const MODERN_ENTROPY_POOL: u64 = 256;
+const PINNED_ENTROPY_LEVEL: f32 = 0.1;
+
+/// CPU temperature that lights a pad fully, in °C.
+const CPU_MAX_TEMPERATURE: f32 = 90.0;
+
+/// Commands can't be run more often than this, in seconds.
+const MIN_COMMAND_INTERVAL: f32 = 1.0;
+
+/// Prints a warning the first time it's asked to, and stays quiet after
+/// that, so a metric that's broken for good doesn't spam every refresh.
+#[derive(Default)]
+pub struct WarnOnce {
+    warned: bool,
+}
+
+impl WarnOnce {
+    pub fn warn(&mut self, message: impl Display) {
+        if !self.warned {
+            eprintln!("{message}");
+            self.warned = true;
+        }
+    }
+
+    /// Whether the warning has been printed already.
+    #[cfg(test)]
+    pub const fn warned(&self) -> bool { self.warned }
+}
+
+/// Reads the pressure stall average for `resource`, warning once if the
+/// kernel doesn't provide it.
+fn pressure(resource: &str, warning: &mut WarnOnce) -> f32 {
+    procfs::pressure(resource).map_or_else(
+        || {
+            warning.warn(format_args!(
+                "/proc/pressure/{resource} is unavailable; PSI pads stay \
+                 dark."
+            ));
+            0.0
+        },
+        |pressure| pressure.clamp(0.0, 1.0),
+    )
+}
+
+/// Usage of the filesystem containing `path`, measured by the function
+/// `make_check` returns. The check runs in the background, so a hung
+/// network mount only darkens this one pad.
+fn filesystem_usage<C>(
+    path: &Path,
+    poller: &mut Option<Poller<Option<f32>>>,
+    warning: &mut WarnOnce,
+    make_check: impl FnOnce() -> C,
+) -> f32
+where
+    C: FnMut(&Path) -> Option<f32> + Send + 'static,
+{
+    let poller = poller.get_or_insert_with(|| {
+        let path = path.to_path_buf();
+        let mut check = make_check();
+        Poller::spawn(DISK_POLL_INTERVAL, move || check(&path))
+    });
+    let latest = poller.latest().copied();
+    match latest {
+        _ if poller.age() > DISK_POLL_TIMEOUT => {
+            warning.warn(format_args!(
+                "Checking the filesystem at {} timed out.",
+                path.display()
+            ));
+            0.0
+        }
+        Some(Some(usage)) => usage,
+        Some(None) => {
+            warning.warn(format_args!(
+                "{} isn't on any mounted filesystem.",
+                path.display()
+            ));
+            0.0
+        }
+        None => 0.0,
+    }
+}
+
+fn validate_uptime_steps(steps: &[UptimeStep]) -> Result<(), String> {
+    if steps.iter().any(|step| !(0.0..=1.0).contains(&step.level)) {
+        return Err("step levels must be between 0 and 1".into());
+    }
+    if steps.iter().any(|step| step.after_hours.is_nan()) {
+        return Err("after_hours must be a number".into());
+    }
+    if steps.first().is_some_and(|step| step.after_hours < 0.0) {
+        return Err("after_hours can't be negative".into());
+    }
+    if steps
+        .windows(2)
+        .any(|pair| pair[1].after_hours <= pair[0].after_hours)
+    {
+        return Err("steps must be in increasing order of after_hours".into());
+    }
+    Ok(())
+}
+
+/// Whether any network interface matches `interface`, warning once if
+/// none does. Without a pattern, there's nothing to miss.
+fn has_interface(
+    system: &System,
+    interface: Option<&str>,
+    warning: &mut WarnOnce,
+) -> bool {
+    let Some(interface) = interface else {
+        return true;
+    };
+    let found = system.has_network_interface(interface);
+    if !found {
+        warning
+            .warn(format_args!("No network interface matches {interface:?}."));
+    }
+    found
+}
+
+/// Share of the open file limit in use, either system-wide or by the
+/// fullest process called `process`. Processes are looked up by name on
+/// every refresh, so a restarted service is picked up again.
+#[allow(clippy::cast_precision_loss)]
+fn file_descriptors(system: &System, process: Option<&str>) -> f32 {
+    let fraction = |(open, limit): (u64, u64)| {
+        if limit == 0 {
+            0.0
+        } else {
+            (open as f32 / limit as f32).clamp(0.0, 1.0)
+        }
+    };
+    let Some(name) = process else {
+        return procfs::file_handles().map_or(0.0, fraction);
+    };
+    system
+        .get_processes()
+        .iter()
+        .filter(|(_, candidate)| candidate.name() == name)
+        .filter_map(|(&pid, _)| procfs::process_file_descriptors(pid))
+        .map(fraction)
+        .fold(0.0, f32::max)
+}
+
+/// Temperature of the first sensor whose label contains `sensor`, or is
+/// exactly `sensor` if `exact`, relative to `max`; warns once with the
+/// available labels if there isn't one.
+fn component_temperature(
+    system: &System,
+    sensor: &str,
+    exact: bool,
+    max: f32,
+    warning: &mut WarnOnce,
+) -> f32 {
+    system
+        .get_component_temperature_percent(
+            |label| {
+                if exact {
+                    label == sensor
+                } else {
+                    label.contains(sensor)
+                }
+            },
+            max,
+        )
+        .unwrap_or_else(|| {
+            let labels: Vec<&str> = system
+                .get_components()
+                .iter()
+                .map(ComponentExt::get_label)
+                .collect();
+            warning.warn(format_args!(
+                "No temperature sensor matches \"{}\"; set `sensor` to one \
+                 of: {}",
+                sensor,
+                labels.join(", ")
+            ));
+            0.0
+        })
+}
+
+/// How much of a countdown of `minutes` has passed, blinking once it's
+/// over; 0 until it's been started.
+fn countdown(started: Option<Instant>, minutes: f32) -> f32 {
+    let Some(started) = started else {
+        return 0.0;
+    };
+    let elapsed = started.elapsed().as_secs_f32();
+    let total = minutes * 60.0;
+    if elapsed < total {
+        elapsed / total
+    } else if (elapsed - total).fract() < 0.5 {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Brightness for the latest SMART check: full if any drive has bad
+/// sectors, or `sectors / max_sectors` if that's set. Drives that couldn't
+/// be checked are left out, after a warning.
+#[allow(clippy::cast_precision_loss)]
+fn smart_alarm(
+    results: Option<&Vec<Result<u64, String>>>,
+    max_sectors: Option<u64>,
+    warning: &mut WarnOnce,
+) -> f32 {
+    let mut sectors = 0u64;
+    for result in results.into_iter().flatten() {
+        match result {
+            Ok(count) => sectors = sectors.saturating_add(*count),
+            Err(err) => warning.warn(err),
+        }
+    }
+    match max_sectors {
+        Some(max) => (sectors as f32 / max as f32).min(1.0),
+        None if sectors > 0 => 1.0,
+        None => 0.0,
+    }
+}
+
+/// Available entropy relative to the kernel's pool size.
+#[allow(clippy::cast_precision_loss)]
+fn entropy() -> f32 {
+    match procfs::entropy() {
+        Some((available, pool))
+            if pool == MODERN_ENTROPY_POOL && available >= pool =>
+        {
+            PINNED_ENTROPY_LEVEL
+        }
+        Some((available, pool)) if pool > 0 => {
+            (available as f32 / pool as f32).clamp(0.0, 1.0)
+        }
+        _ => 0.0,
+    }
+}
+
+/// Fraction of CPU time spent in the state picked out by `field` since the
+/// previous call; 0 on the first call.
+fn cpu_time_fraction(
+    previous: &mut Option<CpuTimes>,
+    field: fn(&CpuTimes) -> u64,
+) -> f32 {
+    let Some(current) = procfs::cpu_times() else {
+        return 0.0;
+    };
+    let fraction = previous.map_or(0.0, |previous| {
+        let total = current.total.saturating_sub(previous.total);
+        let spent = field(&current).saturating_sub(field(&previous));
+        if total == 0 {
+            0.0
+        } else {
+            #[allow(clippy::cast_precision_loss)]
+            (spent as f32 / total as f32).clamp(0.0, 1.0)
+        }
+    });
+    *previous = Some(current);
+    fraction
+}
+
+/// How to combine readings when a metric has several sources.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregate {
+    #[default]
+    Max,
+    Average,
+}
+
+impl Aggregate {
+    /// Combines `values`; an empty iterator gives 0.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn apply(self, values: impl Iterator<Item = f32>) -> f32 {
+        let (count, sum, max) =
+            values.fold((0usize, 0f32, 0f32), |(count, sum, max), value| {
+                (count + 1, sum + value, max.max(value))
+            });
+        match self {
+            Self::Max => max,
+            Self::Average if count > 0 => sum / count as f32,
+            Self::Average => 0.0,
+        }
+    }
+}
+
+/// What counts as used memory.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryMode {
+    /// Everything that isn't available to new allocations.
+    #[default]
+    Available,
+    /// Everything the kernel reports as used, page cache included.
+    Used,
+}
+
+/// A value that can be displayed on a pad.
+///
+/// In the config file, the variant is selected by the `metric` key, and
+/// any parameters sit next to it.
+#[derive(Deserialize)]
+#[serde(tag = "metric", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Metric {
+    CpuUsage,
+    /// Usage of the busiest logical core, which a single-threaded job can
+    /// pin while the average stays low.
+    CpuMaxCore,
+    /// Average usage of the logical `cores`, counted from 0.
+    CpuCores {
+        cores: Vec<usize>,
+    },
+    /// One-minute load average divided by the number of logical cores.
+    LoadAverage,
+    /// Average core frequency, relative to `max_mhz`. Defaults to the
+    /// highest frequency the cores are rated for, where that's known.
+    CpuFrequency {
+        #[serde(default)]
+        max_mhz: Option<f32>,
+    },
+    /// Share of CPU time spent waiting for I/O. Linux only.
+    Iowait {
+        #[serde(skip)]
+        previous: Option<CpuTimes>,
+    },
+    /// Share of CPU time taken away by the hypervisor. Always 0 on bare
+    /// metal. Linux only.
+    Steal {
+        #[serde(skip)]
+        previous: Option<CpuTimes>,
+    },
+    /// Context switches per second, relative to `ceiling`. Linux only.
+    ContextSwitches {
+        #[serde(default = "default_context_switch_ceiling")]
+        ceiling: f32,
+        #[serde(skip)]
+        counter: Counter,
+    },
+    /// CPU usage of the cgroup v2 group at `path`, relative to its quota,
+    /// or to all CPUs if it has none. Relative paths start at
+    /// `/sys/fs/cgroup`. Linux only.
+    CgroupCpu {
+        path: PathBuf,
+        #[serde(skip)]
+        counter: Counter,
+    },
+    /// CPU package power draw from the RAPL energy counter of `zone`,
+    /// relative to `tdp` in watts. The counters are usually only readable
+    /// by root. Linux only.
+    PackagePower {
+        #[serde(default = "default_rapl_zone")]
+        zone: String,
+        #[serde(default = "default_tdp")]
+        tdp: f32,
+        #[serde(skip)]
+        counter: Counter,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    /// Lights up for `hold` seconds whenever a core or package gets
+    /// thermally throttled. Linux only.
+    Throttling {
+        #[serde(default = "default_throttle_hold")]
+        hold: f32,
+        #[serde(skip)]
+        latch: Latch,
+    },
+    /// Lights up for `hold` seconds whenever the OOM killer strikes.
+    /// Linux only.
+    OomKills {
+        #[serde(default = "default_oom_hold")]
+        hold: f32,
+        #[serde(skip)]
+        latch: Latch,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    /// Interrupts per second, relative to `ceiling`. Linux only.
+    Interrupts {
+        #[serde(default = "default_interrupt_ceiling")]
+        ceiling: f32,
+        #[serde(skip)]
+        counter: Counter,
+    },
+    /// Major page faults per second, relative to `ceiling`. Linux only.
+    PageFaults {
+        #[serde(default = "default_page_fault_ceiling")]
+        ceiling: f32,
+        #[serde(skip)]
+        counter: Counter,
+    },
+    /// Entropy available to the kernel's random number generator. Linux
+    /// only.
+    Entropy,
+    /// Pressure stall information: the share of the last ten seconds in
+    /// which some task was stalled waiting for the CPU, memory or I/O.
+    /// Linux only.
+    PsiCpu {
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    PsiMemory {
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    PsiIo {
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    /// Combined CPU usage of every process with the given name, relative
+    /// to all cores.
+    ProcessCpu {
+        name: String,
+    },
+    /// Number of running processes, relative to `max`.
+    ProcessCount {
+        #[serde(default = "default_max_processes")]
+        max: usize,
+    },
+    /// Number of zombie processes: dark up to `ignore`, which a busy
+    /// system has now and then, and full brightness from `max` on.
+    Zombies {
+        #[serde(default = "default_zombies_ignored")]
+        ignore: usize,
+        #[serde(default = "default_max_zombies")]
+        max: usize,
+    },
+    /// Logged in SSH sessions, relative to `max`.
+    SshSessions {
+        #[serde(default = "default_max_ssh_sessions")]
+        max: usize,
+    },
+    /// Temperature of the first sensor whose label contains `sensor`.
+    CpuTemperature {
+        #[serde(default = "default_temperature_sensor")]
+        sensor: String,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    /// Temperature of the sensor labelled exactly `label`, like `Tccd1`,
+    /// relative to `max` in °C.
+    Temperature {
+        label: String,
+        #[serde(default = "default_sensor_max_temperature")]
+        max: f32,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    /// Open files relative to the limit: system-wide, or, with `process`,
+    /// the fullest of the processes with that name. Linux only.
+    FileDescriptors {
+        #[serde(default)]
+        process: Option<String>,
+    },
+    /// Temperature of the first sensor whose label contains `sensor`,
+    /// relative to `max` in °C. Drives run much cooler than CPUs, hence
+    /// the lower default.
+    DriveTemperature {
+        #[serde(default = "default_drive_sensor")]
+        sensor: String,
+        #[serde(default = "default_drive_max_temperature")]
+        max: f32,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    /// Reading of the hottest sensor, whichever it is, relative to `max`
+    /// in °C.
+    TemperatureMax {
+        #[serde(default = "default_max_temperature")]
+        max: f32,
+    },
+    MemoryUsage {
+        #[serde(default)]
+        mode: MemoryMode,
+    },
+    /// Uptime, mapped through `steps` so the pad only changes at
+    /// milestones. Meant to hold steady, so it isn't smoothed.
+    Uptime {
+        #[serde(default = "default_uptime_steps")]
+        steps: Vec<UptimeStep>,
+    },
+    /// Memory usage, not counting the ZFS ARC. Linux only.
+    MemoryExcludingArc,
+    /// Size of the ZFS ARC relative to its maximum. Linux only.
+    ZfsArc,
+    SwapUsage,
+    /// Pages swapped in and out per second, relative to `ceiling`, or to
+    /// `ceiling_mb` in MB/s if that's set. Linux only.
+    SwapActivity {
+        #[serde(default = "default_swap_ceiling")]
+        ceiling: f32,
+        #[serde(default)]
+        ceiling_mb: Option<f32>,
+        #[serde(skip)]
+        counter: Counter,
+    },
+    /// Memory waiting to be written to disk, relative to `ceiling_mb`, or
+    /// by default to the point where the kernel throttles writers. Linux
+    /// only.
+    DirtyMemory {
+        #[serde(default)]
+        ceiling_mb: Option<f32>,
+    },
+    /// Combined resident memory of every process with the given name,
+    /// relative to total memory.
+    ProcessMemory {
+        name: String,
+    },
+    /// Blinks once per second; driven by its own thread rather than the
+    /// sampler.
+    Clock,
+    /// Beats twice every few seconds while the sampler and every other
+    /// pad are working, and blinks fast once one of them gets stuck.
+    /// Driven by a watchdog thread rather than the sampler.
+    Heartbeat,
+    /// A countdown of `minutes` that fills the pad up, then blinks until
+    /// it's restarted. It's idle until the first `SIGUSR1`, and every
+    /// further one starts it over.
+    Timer {
+        #[serde(default = "default_timer_minutes")]
+        minutes: f32,
+        #[serde(skip)]
+        started: Option<Instant>,
+        #[serde(skip)]
+        resets: usize,
+    },
+    /// Lights up while packets are being sent or received. `interface`
+    /// picks the interfaces by name, with `*` and `?` wildcards; by
+    /// default, all of them count.
+    NetworkTransmitted {
+        #[serde(default)]
+        interface: Option<String>,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    NetworkReceived {
+        #[serde(default)]
+        interface: Option<String>,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    NetworkTransmittedErrors,
+    NetworkReceivedErrors,
+    /// TCP sockets in any of `states`, relative to `max`. The socket
+    /// tables can be large, so they're read every `interval` seconds on
+    /// a thread of their own. Linux only.
+    TcpConnections {
+        #[serde(default = "default_max_tcp_connections")]
+        max: usize,
+        #[serde(default = "default_tcp_states")]
+        states: Vec<TcpState>,
+        #[serde(default = "default_tcp_interval")]
+        interval: f32,
+        #[serde(skip)]
+        poller: Option<Poller<Option<usize>>>,
+    },
+    /// Brightens as the TLS certificate served on `host`:`port` nears its
+    /// expiry: dark with `warn_days` or more left, full once it's expired
+    /// or if it can't be fetched at all. Checked every `interval` hours
+    /// with the `openssl` tool.
+    TlsExpiry {
+        host: String,
+        #[serde(default = "default_tls_port")]
+        port: u16,
+        #[serde(default = "default_tls_warn_days")]
+        warn_days: f32,
+        #[serde(default = "default_tls_interval")]
+        interval: f32,
+        #[serde(skip)]
+        poller: Option<Poller<Option<Duration>>>,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    /// State of the UPS called `ups`, as reported by the Network UPS Tools
+    /// daemon on `host`. Dark on line power, showing the charge used up
+    /// while on battery, and full on low battery or when `upsd` can't be
+    /// reached.
+    Nut {
+        ups: String,
+        #[serde(default = "default_nut_host")]
+        host: String,
+        #[serde(default = "default_nut_port")]
+        port: u16,
+        #[serde(default = "default_nut_interval")]
+        interval: f32,
+        #[serde(skip)]
+        poller: Option<Poller<io::Result<UpsState>>>,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    /// Full brightness while the tunnel `interface` is up. If `wg` reports
+    /// no handshake in `stale_after` seconds, it shows half brightness
+    /// instead.
+    Vpn {
+        interface: String,
+        #[serde(default = "default_vpn_stale_after")]
+        stale_after: f32,
+        #[serde(skip)]
+        poller: Option<Poller<f32>>,
+    },
+    /// Signal strength of the Wi-Fi connection, from `min_dbm` (dark) to
+    /// `max_dbm` (full brightness). Linux only.
+    WifiSignal {
+        #[serde(default = "default_wifi_min_dbm")]
+        min_dbm: f32,
+        #[serde(default = "default_wifi_max_dbm")]
+        max_dbm: f32,
+    },
+    /// Errors and dropped packets, received and transmitted, across all
+    /// interfaces per second, relative to `ceiling`.
+    NetworkErrorRate {
+        #[serde(default = "default_network_error_ceiling")]
+        ceiling: f32,
+        #[serde(skip)]
+        counter: Counter,
+    },
+    /// Combined traffic across the interfaces `interface` picks, relative
+    /// to the link capacity in Mbit/s.
+    NetworkThroughput {
+        #[serde(default = "default_link_capacity")]
+        capacity: f32,
+        #[serde(default)]
+        interface: Option<String>,
+        #[serde(skip)]
+        counter: Counter,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    /// Used space of the filesystem containing `path`.
+    ///
+    /// The disks are checked on their own thread, so a hung network mount
+    /// only darkens this pad instead of stalling every other one.
+    DiskUsage {
+        #[serde(default = "default_disk_path", alias = "mount")]
+        path: PathBuf,
+        #[serde(skip)]
+        poller: Option<Poller<Option<f32>>>,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    /// Age of the file at `path`, relative to `max_hours`; a missing file
+    /// reads as fully overdue. Handy with a marker file that a backup job
+    /// touches when it succeeds.
+    FileAge {
+        path: PathBuf,
+        #[serde(default = "default_file_max_hours")]
+        max_hours: f32,
+    },
+    /// Share of inodes in use on the filesystem containing `path`.
+    InodeUsage {
+        #[serde(default = "default_disk_path")]
+        path: PathBuf,
+        #[serde(skip)]
+        poller: Option<Poller<Option<f32>>>,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    /// Dark while every md array is healthy, the progress of a resync or
+    /// recovery while one runs, and full brightness if an array is
+    /// degraded. Linux only.
+    Mdraid {
+        #[serde(skip)]
+        poller: Option<Poller<Option<f32>>>,
+    },
+    /// Lights up when any of `devices` reports bad sectors through
+    /// `smartctl`, checked every `interval` seconds. With `max_sectors`,
+    /// the brightness follows the count instead.
+    Smart {
+        devices: Vec<String>,
+        #[serde(default)]
+        max_sectors: Option<u64>,
+        #[serde(default = "default_smart_interval")]
+        interval: f32,
+        #[serde(skip)]
+        poller: Option<Poller<Vec<Result<u64, String>>>>,
+        #[serde(skip)]
+        warning: WarnOnce,
+    },
+    /// Combined read and write throughput of all physical disks, relative
+    /// to a ceiling in bytes per second.
+    DiskIo {
+        #[serde(default = "default_disk_io_ceiling")]
+        ceiling: f32,
+        #[serde(skip)]
+        counter: Counter,
+    },
+    /// Utilization of an NVIDIA GPU. Needs the `gpu` feature.
+    GpuUsage {
+        #[serde(default)]
+        device: u32,
+    },
+    /// Share of the GPU's video memory in use.
+    GpuMemory {
+        #[serde(default)]
+        device: u32,
+    },
+    /// Temperature of the GPU, relative to `max` in °C. Uses the hwmon
+    /// sensors if there are any, and NVML otherwise.
+    GpuTemperature {
+        #[serde(default = "default_gpu_max_temperature")]
+        max: f32,
+        #[serde(default)]
+        device: u32,
+    },
+    /// Charge of the laptop battery; 0 on machines without one.
+    Battery,
+    /// Power drawn from the battery, relative to `max_watts`. Dark on AC
+    /// power.
+    BatteryDischarge {
+        #[serde(default = "default_battery_max_watts")]
+        max_watts: f32,
+    },
+    /// Fully on while on AC power, off while running on the battery.
+    Charging,
+    /// Speed of the case and CPU fans, relative to each fan's maximum if
+    /// the driver reports one, or to `max_rpm` otherwise.
+    FanSpeed {
+        #[serde(default = "default_fan_max_rpm")]
+        max_rpm: f32,
+        #[serde(default)]
+        aggregate: Aggregate,
+    },
+    /// Round-trip time to `host`, relative to `max_ms`. Lost packets read
+    /// as fully on.
+    Ping {
+        host: String,
+        #[serde(default = "default_ping_max_ms")]
+        max_ms: f32,
+        #[serde(skip)]
+        poller: Option<Poller<Option<Duration>>>,
+    },
+    /// Response time of a GET request to `url`, relative to `timeout`
+    /// in seconds. Errors, including 4xx and 5xx responses, read as
+    /// fully on.
+    Http {
+        url: String,
+        #[serde(default = "default_http_timeout")]
+        timeout: f32,
+        #[serde(default = "default_http_interval")]
+        interval: f32,
+        #[serde(skip)]
+        poller: Option<Poller<Option<Duration>>>,
+    },
+    /// Time it takes to look up `host`, relative to `max_ms`, every
+    /// `interval` seconds. Asks `server` if there is one, or the system
+    /// resolver otherwise. Failed lookups read as fully on.
+    Dns {
+        host: String,
+        #[serde(default)]
+        server: Option<String>,
+        #[serde(default = "default_dns_max_ms")]
+        max_ms: f32,
+        #[serde(default = "default_dns_interval")]
+        interval: f32,
+        #[serde(skip)]
+        poller: Option<Poller<Option<Duration>>>,
+    },
+    /// How far the system clock is off, relative to `max_ms`, as reported
+    /// by chrony or systemd-timesyncd. Reads as fully on while they have
+    /// no source to sync to.
+    ClockOffset {
+        #[serde(default = "default_clock_offset_max_ms")]
+        max_ms: f32,
+        #[serde(skip)]
+        poller: Option<Poller<Option<f32>>>,
+    },
+    /// State of a systemd unit: dark while it's active, half-lit while it
+    /// starts or stops, and fully on when it's failed or inactive.
+    SystemdUnit {
+        unit: String,
+        #[serde(default = "default_systemd_interval")]
+        interval: f32,
+        #[serde(skip)]
+        poller: Option<Poller<f32>>,
+    },
+    /// Lights up while any systemd unit has failed, or if `systemctl`
+    /// can't tell. Checked every `interval` seconds.
+    FailedUnits {
+        #[serde(default = "default_failed_units_interval")]
+        interval: f32,
+        #[serde(skip)]
+        poller: Option<Poller<Option<usize>>>,
+    },
+    /// Journal entries with priority `err` or worse in the last minute,
+    /// relative to `ceiling`. Follows `journalctl` in the background.
+    JournalErrors {
+        #[serde(default = "default_journal_ceiling")]
+        ceiling: f32,
+        #[serde(skip)]
+        follower: Option<Follower>,
+    },
+    /// JACK xruns in the last minute, relative to `ceiling`. Follows the
+    /// `log` of `jackdbus` in the background, and stays dark while there
+    /// isn't one.
+    JackXruns {
+        #[serde(default)]
+        log: Option<PathBuf>,
+        #[serde(default = "default_xrun_ceiling")]
+        ceiling: f32,
+        #[serde(skip)]
+        follower: Option<Follower>,
+    },
+    /// Packages with an update available, relative to `ceiling`, asking
+    /// `backend` every `interval` seconds. If it fails, the pad keeps its
+    /// previous value.
+    Updates {
+        backend: updates::Backend,
+        #[serde(default = "default_updates_ceiling")]
+        ceiling: usize,
+        #[serde(default = "default_updates_interval")]
+        interval: f32,
+        #[serde(skip)]
+        poller: Option<Poller<Option<usize>>>,
+        #[serde(skip)]
+        value: f32,
+    },
+    /// Runs `command` through the shell every `interval` seconds and shows
+    /// its output, a number from 0 to `scale`. If the command fails or
+    /// prints something else, the pad keeps its previous value.
+    Command {
+        command: String,
+        #[serde(default = "default_command_interval")]
+        interval: f32,
+        #[serde(default = "default_command_timeout")]
+        timeout: f32,
+        #[serde(default = "default_command_scale")]
+        scale: f32,
+        #[serde(skip)]
+        poller: Option<Poller<Option<f32>>>,
+        #[serde(skip)]
+        value: f32,
+    },
+    /// Share of the `expected` Docker containers that aren't running, so
+    /// a healthy stack stays dark. Shows `unreachable` while the daemon
+    /// can't be reached.
+    Docker {
+        #[serde(default = "default_docker_host")]
+        host: String,
+        #[serde(default = "default_docker_expected")]
+        expected: usize,
+        #[serde(default = "default_docker_unreachable")]
+        unreachable: f32,
+        #[serde(skip)]
+        poller: Option<Poller<Option<usize>>>,
+    },
+}
+
+pub fn default_temperature_sensor() -> String { "Package id".to_string() }
+
+const fn default_sensor_max_temperature() -> f32 { CPU_MAX_TEMPERATURE }
+
+fn default_uptime_steps() -> Vec<UptimeStep> {
+    // Off for the first hour, then faint, medium after a day, brighter
+    // after a week, and full after 30 days.
+    [(1.0, 0.1), (24.0, 0.4), (168.0, 0.7), (720.0, 1.0)]
+        .iter()
+        .map(|&(after_hours, level)| UptimeStep { after_hours, level })
+        .collect()
+}
+
+fn default_drive_sensor() -> String { "nvme".to_string() }
+
+const fn default_drive_max_temperature() -> f32 { 70.0 }
+
+const fn default_timer_minutes() -> f32 { 25.0 }
+
+const fn default_max_temperature() -> f32 { 100.0 }
+
+const fn default_context_switch_ceiling() -> f32 { 100_000.0 }
+
+fn default_rapl_zone() -> String { "intel-rapl:0".to_string() }
+
+const fn default_tdp() -> f32 { 65.0 }
+
+const fn default_throttle_hold() -> f32 { 3.0 }
+
+const fn default_oom_hold() -> f32 { 600.0 }
+
+const fn default_zombies_ignored() -> usize { 2 }
+
+const fn default_max_zombies() -> usize { 10 }
+
+const fn default_max_ssh_sessions() -> usize { 5 }
+
+const fn default_interrupt_ceiling() -> f32 { 50_000.0 }
+
+const fn default_page_fault_ceiling() -> f32 { 1000.0 }
+
+const fn default_swap_ceiling() -> f32 { 1000.0 }
+
+const fn default_max_processes() -> usize { 600 }
+
+const fn default_max_tcp_connections() -> usize { 500 }
+
+fn default_tcp_states() -> Vec<TcpState> { vec![TcpState::Established] }
+
+const fn default_tcp_interval() -> f32 { 2.0 }
+
+const fn default_tls_port() -> u16 { 443 }
+
+const fn default_tls_warn_days() -> f32 { 30.0 }
+
+const fn default_tls_interval() -> f32 { 1.0 }
+
+fn default_nut_host() -> String { "localhost".into() }
+
+const fn default_nut_port() -> u16 { 3493 }
+
+const fn default_nut_interval() -> f32 { 10.0 }
+
+// WireGuard renews sessions every two minutes while there's traffic, and
+// keepalives are usually set well below that.
+const fn default_vpn_stale_after() -> f32 { 180.0 }
+
+const fn default_wifi_min_dbm() -> f32 { -90.0 }
+
+const fn default_wifi_max_dbm() -> f32 { -30.0 }
+
+const fn default_network_error_ceiling() -> f32 { 10.0 }
+
+const fn default_link_capacity() -> f32 { 1000.0 }
+
+fn default_disk_path() -> PathBuf { PathBuf::from("/") }
+
+const fn default_file_max_hours() -> f32 { 26.0 }
+
+const fn default_smart_interval() -> f32 { 600.0 }
+
+const fn default_disk_io_ceiling() -> f32 { 500_000_000.0 }
+
+const fn default_gpu_max_temperature() -> f32 { 100.0 }
+
+const fn default_fan_max_rpm() -> f32 { 2000.0 }
+
+const fn default_ping_max_ms() -> f32 { 100.0 }
+
+const fn default_battery_max_watts() -> f32 { 40.0 }
+
+const fn default_clock_offset_max_ms() -> f32 { 100.0 }
+
+const fn default_dns_max_ms() -> f32 { 200.0 }
+
+const fn default_dns_interval() -> f32 { 10.0 }
+
+const fn default_http_timeout() -> f32 { 2.0 }
+
+const fn default_http_interval() -> f32 { 5.0 }
+
+const fn default_systemd_interval() -> f32 { 5.0 }
+
+const fn default_failed_units_interval() -> f32 { 15.0 }
+
+const fn default_journal_ceiling() -> f32 { 10.0 }
+
+const fn default_updates_ceiling() -> usize { 50 }
+
+const fn default_updates_interval() -> f32 { 1800.0 }
+
+const fn default_xrun_ceiling() -> f32 { 5.0 }
+
+const fn default_command_interval() -> f32 { 10.0 }
+
+const fn default_command_timeout() -> f32 { 5.0 }
+
+const fn default_command_scale() -> f32 { 1.0 }
+
+fn default_docker_host() -> String {
+    std::env::var("DOCKER_HOST")
+        .unwrap_or_else(|_| "/var/run/docker.sock".to_string())
+}
+
+const fn default_docker_expected() -> usize { 1 }
+
+const fn default_docker_unreachable() -> f32 { 1.0 }
+
+/// Whether a setting is a usable positive number: not 0, negative, NaN or
+/// infinite, any of which would break the durations and divisions made
+/// from it.
+fn positive(value: f32) -> bool { value > 0.0 && value.is_finite() }
+
+/// Whether a setting is a finite number from `min` up.
+fn at_least(value: f32, min: f32) -> bool { value >= min && value.is_finite() }
+
+impl Metric {
+    /// Checks the parameters, and whether the metric can work on this
+    /// machine at all.
+    // One arm per check, like `sample`.
+    #[allow(clippy::too_many_lines)]
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Http {
+                timeout, interval, ..
+            } if !positive(*timeout) || !positive(*interval) => {
+                Err("timeout and interval must be positive".into())
+            }
+            Self::Http {
+                timeout, interval, ..
+            } if timeout > interval => {
+                Err("timeout can't be longer than interval".into())
+            }
+            Self::BatteryDischarge { max_watts } if !positive(*max_watts) => {
+                Err("max_watts must be positive".into())
+            }
+            Self::ClockOffset { max_ms, .. } | Self::Ping { max_ms, .. }
+                if !positive(*max_ms) =>
+            {
+                Err("max_ms must be positive".into())
+            }
+            Self::Dns {
+                max_ms, interval, ..
+            } if !positive(*max_ms) || !positive(*interval) => {
+                Err("max_ms and interval must be positive".into())
+            }
+            Self::SystemdUnit { .. } if !systemd::booted() => {
+                Err("systemd_unit needs a system running systemd".into())
+            }
+            Self::JackXruns { log: None, .. }
+                if jack::default_log().is_none() =>
+            {
+                Err("log must be given when HOME isn't set".into())
+            }
+            Self::JournalErrors { ceiling, .. }
+            | Self::JackXruns { ceiling, .. }
+                if !positive(*ceiling) =>
+            {
+                Err("ceiling must be positive".into())
+            }
+            Self::FailedUnits { .. } if !systemd::booted() => {
+                Err("failed_units needs a system running systemd".into())
+            }
+            Self::SystemdUnit { interval, .. }
+            | Self::FailedUnits { interval, .. }
+                if !positive(*interval) =>
+            {
+                Err("interval must be positive".into())
+            }
+            Self::Updates { ceiling: 0, .. } => {
+                Err("ceiling must be at least 1".into())
+            }
+            Self::Updates { interval, .. } if !positive(*interval) => {
+                Err("interval must be positive".into())
+            }
+            Self::Command { interval, .. }
+                if !at_least(*interval, MIN_COMMAND_INTERVAL) =>
+            {
+                Err(format!(
+                    "interval must be at least {MIN_COMMAND_INTERVAL} seconds"
+                ))
+            }
+            Self::Command { timeout, scale, .. }
+                if !positive(*timeout) || !positive(*scale) =>
+            {
+                Err("timeout and scale must be positive".into())
+            }
+            Self::MemoryExcludingArc | Self::ZfsArc
+                if procfs::arcstats().is_none() =>
+            {
+                Err("ZFS isn't loaded: /proc/spl/kstat/zfs/arcstats is \
+                     missing"
+                    .into())
+            }
+            Self::Uptime { steps } => validate_uptime_steps(steps),
+            Self::DirtyMemory {
+                ceiling_mb: Some(ceiling),
+            }
+            | Self::SwapActivity {
+                ceiling_mb: Some(ceiling),
+                ..
+            } if !positive(*ceiling) => {
+                Err("ceiling_mb must be positive".into())
+            }
+            Self::CgroupCpu { path, .. }
+                if !cgroup::exists(&cgroup::resolve(path)) =>
+            {
+                Err(format!(
+                    "{} isn't a cgroup v2 group with CPU accounting",
+                    cgroup::resolve(path).display()
+                ))
+            }
+            Self::Throttling { hold, .. } | Self::OomKills { hold, .. }
+                if !at_least(*hold, 0.0) =>
+            {
+                Err("hold can't be negative".into())
+            }
+            Self::PackagePower { tdp, .. } if !positive(*tdp) => {
+                Err("tdp must be positive".into())
+            }
+            Self::ContextSwitches { ceiling, .. }
+            | Self::PageFaults { ceiling, .. }
+            | Self::Interrupts { ceiling, .. }
+            | Self::SwapActivity { ceiling, .. }
+            | Self::DiskIo { ceiling, .. }
+                if !positive(*ceiling) =>
+            {
+                Err("ceiling must be positive".into())
+            }
+            Self::DriveTemperature { max, .. }
+            | Self::Temperature { max, .. }
+            | Self::TemperatureMax { max }
+            | Self::GpuTemperature { max, .. }
+                if !positive(*max) =>
+            {
+                Err("max must be positive".into())
+            }
+            Self::FanSpeed { max_rpm, .. } if !positive(*max_rpm) => {
+                Err("max_rpm must be positive".into())
+            }
+            Self::FileAge { max_hours, .. } if !positive(*max_hours) => {
+                Err("max_hours must be positive".into())
+            }
+            Self::Timer { minutes, .. } if !positive(*minutes) => {
+                Err("minutes must be positive".into())
+            }
+            Self::TlsExpiry {
+                warn_days,
+                interval,
+                ..
+            } if !positive(*warn_days) || !positive(*interval) => {
+                Err("warn_days and interval must be positive".into())
+            }
+            Self::Nut { interval, .. }
+                if !at_least(*interval, NUT_TIMEOUT.as_secs_f32()) =>
+            {
+                Err(format!(
+                    "interval must be at least {} seconds",
+                    NUT_TIMEOUT.as_secs()
+                ))
+            }
+            Self::Vpn { stale_after, .. } if !positive(*stale_after) => {
+                Err("stale_after must be positive".into())
+            }
+            Self::TcpConnections { max: 0, .. }
+            | Self::SshSessions { max: 0 } => {
+                Err("max must be at least 1".into())
+            }
+            Self::TcpConnections { interval, .. } if !positive(*interval) => {
+                Err("interval must be positive".into())
+            }
+            Self::WifiSignal { min_dbm, max_dbm }
+                if !(min_dbm < max_dbm
+                    && min_dbm.is_finite()
+                    && max_dbm.is_finite()) =>
+            {
+                Err("min_dbm must be below max_dbm".into())
+            }
+            Self::NetworkErrorRate { ceiling, .. } if !positive(*ceiling) => {
+                Err("ceiling must be positive".into())
+            }
+            Self::NetworkThroughput { capacity, .. }
+                if !positive(*capacity) =>
+            {
+                Err("capacity must be positive".into())
+            }
+            Self::CpuCores { cores } if cores.is_empty() => {
+                Err("cores can't be empty".into())
+            }
+            Self::CpuCores { cores } => {
+                let available = utility::logical_cores();
+                cores.iter().find(|&&core| core >= available).map_or(
+                    Ok(()),
+                    |core| {
+                        Err(format!(
+                            "there's no core {core}, only cores 0 to {}",
+                            available.saturating_sub(1)
+                        ))
+                    },
+                )
+            }
+            Self::Smart { devices, .. } if devices.is_empty() => {
+                Err("devices can't be empty".into())
+            }
+            Self::Smart {
+                max_sectors: Some(0),
+                ..
+            } => Err("max_sectors must be at least 1".into()),
+            Self::Smart { interval, .. } if !positive(*interval) => {
+                Err("interval must be positive".into())
+            }
+            // Reading the drives once up front catches a missing smartctl,
+            // missing permissions to open them, or drives without SMART.
+            Self::Smart { devices, .. } => devices
+                .iter()
+                .try_for_each(|device| smart::bad_sectors(device).map(drop)),
+            Self::Zombies { ignore, max } if max <= ignore => {
+                Err("max must be greater than ignore".into())
+            }
+            Self::Docker { expected: 0, .. } => {
+                Err("expected must be at least 1".into())
+            }
+            Self::Docker { unreachable, .. }
+                if !(0.0..=1.0).contains(unreachable) =>
+            {
+                Err("unreachable must be between 0 and 1".into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// How many events the metric has counted so far, for metrics that
+    /// count them.
+    pub fn events(&self) -> Option<u64> {
+        match self {
+            Self::Throttling { latch, .. } | Self::OomKills { latch, .. } => {
+                Some(latch.events())
+            }
+            Self::JournalErrors { follower, .. }
+            | Self::JackXruns { follower, .. } => {
+                Some(follower.as_ref().map_or(0, Follower::total))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the metric isn't sampled at all, but has a thread of its
+    /// own to drive its pads.
+    /// Whether the metric has any settings besides its name, since those
+    /// without any don't check what comes with them.
+    pub const fn has_parameters(&self) -> bool {
+        !matches!(
+            self,
+            Self::CpuUsage
+                | Self::CpuMaxCore
+                | Self::LoadAverage
+                | Self::Entropy
+                | Self::MemoryExcludingArc
+                | Self::ZfsArc
+                | Self::SwapUsage
+                | Self::Clock
+                | Self::Heartbeat
+                | Self::NetworkTransmittedErrors
+                | Self::NetworkReceivedErrors
+                | Self::Battery
+                | Self::Charging
+        )
+    }
+
+    pub const fn is_driven(&self) -> bool {
+        matches!(self, Self::Clock | Self::Heartbeat)
+    }
+
+    pub const fn counts_events(&self) -> bool {
+        matches!(
+            self,
+            Self::Throttling { .. }
+                | Self::OomKills { .. }
+                | Self::JournalErrors { .. }
+                | Self::JackXruns { .. }
+        )
+    }
+
+    /// Whether the metric switches between a few distinct levels, like
+    /// states and alarms do, which smoothing would only blur.
+    pub const fn is_discrete(&self) -> bool {
+        matches!(
+            self,
+            Self::Throttling { .. }
+                | Self::OomKills { .. }
+                | Self::Uptime { .. }
+                | Self::Clock
+                | Self::Heartbeat
+                | Self::Timer { .. }
+                | Self::Nut { .. }
+                | Self::Vpn { .. }
+                | Self::Mdraid { .. }
+                | Self::Smart { .. }
+                | Self::Charging
+                | Self::SystemdUnit { .. }
+                | Self::FailedUnits { .. }
+                | Self::Docker { .. }
+        )
+    }
+
+    /// Adds whatever `sysinfo` needs to refresh for this metric to `kind`.
+    pub fn refresh_kind(&self, kind: RefreshKind) -> RefreshKind {
+        match self {
+            Self::CpuUsage
+            | Self::CpuMaxCore
+            | Self::CpuCores { .. }
+            | Self::LoadAverage
+            | Self::CpuFrequency { .. } => kind.with_cpu(),
+            Self::ProcessCpu { .. } => kind.with_cpu().with_processes(),
+            Self::ProcessCount { .. }
+            | Self::Zombies { .. }
+            | Self::SshSessions { .. }
+            | Self::FileDescriptors { process: Some(_) } => {
+                kind.with_processes()
+            }
+            Self::CpuTemperature { .. }
+            | Self::GpuTemperature { .. }
+            | Self::DriveTemperature { .. }
+            | Self::Temperature { .. }
+            | Self::TemperatureMax { .. } => kind.with_components(),
+            // sysinfo only updates the uptime along with some other
+            // refresh, and memory is the cheapest one.
+            Self::MemoryUsage { .. }
+            | Self::Uptime { .. }
+            | Self::MemoryExcludingArc
+            | Self::SwapUsage => kind.with_memory(),
+            Self::ProcessMemory { .. } => kind.with_memory().with_processes(),
+            Self::Clock
+            | Self::Heartbeat
+            | Self::Timer { .. }
+            | Self::FileAge { .. }
+            | Self::DiskIo { .. }
+            | Self::GpuUsage { .. }
+            | Self::GpuMemory { .. }
+            | Self::Battery
+            | Self::BatteryDischarge { .. }
+            | Self::Charging
+            | Self::FanSpeed { .. }
+            | Self::Iowait { .. }
+            | Self::Steal { .. }
+            | Self::ContextSwitches { .. }
+            | Self::CgroupCpu { .. }
+            | Self::PackagePower { .. }
+            | Self::Throttling { .. }
+            | Self::OomKills { .. }
+            | Self::PageFaults { .. }
+            | Self::Interrupts { .. }
+            | Self::SwapActivity { .. }
+            | Self::Entropy
+            | Self::FileDescriptors { process: None }
+            | Self::WifiSignal { .. }
+            | Self::TcpConnections { .. }
+            | Self::DirtyMemory { .. }
+            | Self::Mdraid { .. }
+            | Self::Smart { .. }
+            | Self::Vpn { .. }
+            | Self::TlsExpiry { .. }
+            | Self::Nut { .. }
+            | Self::ZfsArc
+            | Self::PsiCpu { .. }
+            | Self::PsiMemory { .. }
+            | Self::PsiIo { .. }
+            | Self::DiskUsage { .. }
+            | Self::InodeUsage { .. }
+            | Self::Ping { .. }
+            | Self::Http { .. }
+            | Self::Dns { .. }
+            | Self::ClockOffset { .. }
+            | Self::SystemdUnit { .. }
+            | Self::FailedUnits { .. }
+            | Self::JournalErrors { .. }
+            | Self::JackXruns { .. }
+            | Self::Updates { .. }
+            | Self::Command { .. }
+            | Self::Docker { .. } => kind,
+            Self::NetworkTransmitted { .. }
+            | Self::NetworkReceived { .. }
+            | Self::NetworkTransmittedErrors
+            | Self::NetworkReceivedErrors
+            | Self::NetworkErrorRate { .. }
+            | Self::NetworkThroughput { .. } => kind.with_networks(),
+        }
+    }
+
+    // One arm per metric; anything longer than a few lines lives in its own
+    // function.
+    #[allow(clippy::too_many_lines)]
+    pub fn sample(&mut self, system: &System) -> f32 {
+        match self {
+            Self::CpuUsage => system.get_cpu_usage_percent() / 100.0,
+            Self::CpuMaxCore => system.get_max_core_usage_percent() / 100.0,
+            Self::CpuCores { cores } => {
+                system.get_cores_usage_percent(cores) / 100.0
+            }
+            Self::LoadAverage => system.get_load_average_percent(),
+            Self::CpuFrequency { max_mhz } => max_mhz
+                .or_else(procfs::cpu_max_frequency_mhz)
+                .map_or(0.0, |max_mhz| {
+                    system.get_cpu_frequency_percent(max_mhz)
+                }),
+            Self::Iowait { previous } => {
+                cpu_time_fraction(previous, |times| times.iowait)
+            }
+            Self::Steal { previous } => {
+                cpu_time_fraction(previous, |times| times.steal)
+            }
+            Self::Interrupts { ceiling, counter } => procfs::interrupts()
+                .map_or(0.0, |interrupts| {
+                    // A 64-bit total never wraps, so going backwards is a
+                    // reset.
+                    (counter.rate(interrupts) / *ceiling).clamp(0.0, 1.0)
+                }),
+            Self::PageFaults { ceiling, counter } => {
+                procfs::major_page_faults().map_or(0.0, |faults| {
+                    (counter.rate(faults) / *ceiling).clamp(0.0, 1.0)
+                })
+            }
+            Self::ContextSwitches { ceiling, counter } => {
+                procfs::context_switches().map_or(0.0, |switches| {
+                    (counter.rate(switches) / *ceiling).clamp(0.0, 1.0)
+                })
+            }
+            Self::CgroupCpu { path, counter } => {
+                let group = cgroup::resolve(path);
+                // A recreated group starts counting from 0 again, which the
+                // counter treats as a reset.
+                cgroup::cpu_usage_usec(&group).map_or(0.0, |usage| {
+                    let cpus = counter.rate(usage) / 1_000_000.0;
+                    (cpus / cgroup::cpu_limit(&group)).clamp(0.0, 1.0)
+                })
+            }
+            Self::PackagePower {
+                zone,
+                tdp,
+                counter,
+                warning,
+            } => match rapl::energy_uj(zone) {
+                Ok((energy, range)) => {
+                    let watts = counter.wrapping_rate(energy, range) / 1e6;
+                    (watts / *tdp).clamp(0.0, 1.0)
+                }
+                Err(err) => {
+                    warning.warn(format_args!(
+                        "Can't read the energy counter of RAPL zone {zone}: \
+                         {err}"
+                    ));
+                    0.0
+                }
+            },
+            Self::Throttling { hold, latch } => latch.update(
+                procfs::throttle_events(),
+                Duration::from_secs_f32(*hold),
+            ),
+            Self::OomKills {
+                hold,
+                latch,
+                warning,
+            } => {
+                let kills = procfs::oom_kills();
+                if kills.is_none() {
+                    warning.warn(
+                        "This kernel doesn't count OOM kills in /proc/vmstat.",
+                    );
+                }
+                latch.update(kills, Duration::from_secs_f32(*hold))
+            }
+            Self::Entropy => entropy(),
+            Self::PsiCpu { warning } => pressure("cpu", warning),
+            Self::PsiMemory { warning } => pressure("memory", warning),
+            Self::PsiIo { warning } => pressure("io", warning),
+            Self::ProcessCpu { name } => system.get_process_cpu_percent(name),
+            Self::ProcessCount { max } => {
+                system.get_process_count_percent(*max)
+            }
+            #[allow(clippy::cast_precision_loss)]
+            Self::SshSessions { max } => {
+                (system.get_ssh_session_count() as f32 / *max as f32).min(1.0)
+            }
+            #[allow(clippy::cast_precision_loss)]
+            Self::Zombies { ignore, max } => {
+                let zombies =
+                    system.get_zombie_count().saturating_sub(*ignore);
+                (zombies as f32 / (*max - *ignore) as f32).clamp(0.0, 1.0)
+            }
+            Self::CpuTemperature { sensor, warning } => component_temperature(
+                system,
+                sensor,
+                false,
+                CPU_MAX_TEMPERATURE,
+                warning,
+            ),
+            Self::Temperature {
+                label,
+                max,
+                warning,
+            } => component_temperature(system, label, true, *max, warning),
+            Self::DriveTemperature {
+                sensor,
+                max,
+                warning,
+            } => component_temperature(system, sensor, false, *max, warning),
+            Self::TemperatureMax { max } => {
+                system.get_max_temperature().map_or(0.0, |temperature| {
+                    (temperature / *max).clamp(0.0, 1.0)
+                })
+            }
+            Self::FileDescriptors { process } => {
+                file_descriptors(system, process.as_deref())
+            }
+            Self::MemoryUsage {
+                mode: MemoryMode::Available,
+            } => system.get_memory_pressure_percent(),
+            Self::MemoryUsage {
+                mode: MemoryMode::Used,
+            } => system.get_memory_usage_percent(),
+            Self::Uptime { steps } => system.get_uptime_percent(steps),
+            Self::MemoryExcludingArc => {
+                let arc = procfs::arcstats().map_or(0, |arc| arc.size);
+                system.get_memory_usage_excluding_percent(arc)
+            }
+            #[allow(clippy::cast_precision_loss)]
+            Self::ZfsArc => match procfs::arcstats() {
+                Some(arc) if arc.c_max > 0 => {
+                    (arc.size as f32 / arc.c_max as f32).clamp(0.0, 1.0)
+                }
+                _ => 0.0,
+            },
+            Self::SwapUsage => system.get_swap_usage_percent(),
+            #[allow(clippy::cast_precision_loss)]
+            Self::SwapActivity {
+                ceiling,
+                ceiling_mb,
+                counter,
+            } => procfs::swap_pages().map_or(0.0, |pages| {
+                let ceiling = ceiling_mb.map_or(*ceiling, |mb| {
+                    mb * 1_000_000.0 / procfs::page_size() as f32
+                });
+                (counter.rate(pages) / ceiling).clamp(0.0, 1.0)
+            }),
+            #[allow(clippy::cast_precision_loss)]
+            Self::DirtyMemory { ceiling_mb } => {
+                // sysinfo reads /proc/meminfo too, but doesn't keep these.
+                procfs::dirty_memory().map_or(0.0, |dirty| {
+                    let limit = ceiling_mb
+                        .map_or(dirty.limit as f32, |mb| mb * 1_000_000.0);
+                    if limit > 0.0 {
+                        (dirty.pending as f32 / limit).clamp(0.0, 1.0)
+                    } else {
+                        0.0
+                    }
+                })
+            }
+            Self::ProcessMemory { name } => {
+                system.get_process_memory_percent(name)
+            }
+            Self::Clock | Self::Heartbeat => 0.0,
+            Self::Timer {
+                minutes,
+                started,
+                resets,
+            } => {
+                let current = timer::resets();
+                if current != *resets {
+                    *resets = current;
+                    *started = Some(Instant::now());
+                }
+                countdown(*started, *minutes)
+            }
+            Self::NetworkTransmitted { interface, warning } => {
+                let interface = interface.as_deref();
+                if has_interface(system, interface, warning) {
+                    system.get_network_transmitted_percent(interface)
+                } else {
+                    0.0
+                }
+            }
+            Self::NetworkReceived { interface, warning } => {
+                let interface = interface.as_deref();
+                if has_interface(system, interface, warning) {
+                    system.get_network_received_percent(interface)
+                } else {
+                    0.0
+                }
+            }
+            Self::NetworkTransmittedErrors => {
+                system.get_network_transmitted_error_percent()
+            }
+            Self::NetworkReceivedErrors => {
+                system.get_network_received_error_percent()
+            }
+            #[allow(clippy::cast_precision_loss)]
+            Self::TcpConnections {
+                max,
+                states,
+                interval,
+                poller,
+            } => {
+                let poller = poller.get_or_insert_with(|| {
+                    let states = states.clone();
+                    let interval = Duration::from_secs_f32(*interval);
+                    Poller::spawn(interval, move || {
+                        procfs::tcp_connections(&states)
+                    })
+                });
+                match poller.latest() {
+                    Some(Some(count)) => {
+                        (*count as f32 / *max as f32).clamp(0.0, 1.0)
+                    }
+                    _ => 0.0,
+                }
+            }
+            Self::TlsExpiry {
+                host,
+                port,
+                warn_days,
+                interval,
+                poller,
+                warning,
+            } => {
+                let poller = poller.get_or_insert_with(|| {
+                    let host = host.clone();
+                    let port = *port;
+                    let interval = Duration::from_secs_f32(*interval * 3600.0);
+                    Poller::spawn(interval, move || {
+                        probe::tls_expiry(&host, port, TLS_TIMEOUT)
+                    })
+                });
+                match poller.latest() {
+                    Some(Some(left)) => {
+                        let days = left.as_secs_f32() / 86_400.0;
+                        (1.0 - days / *warn_days).clamp(0.0, 1.0)
+                    }
+                    Some(None) => {
+                        warning.warn(format_args!(
+                            "Couldn't fetch the certificate of {host}:{port}."
+                        ));
+                        1.0
+                    }
+                    None => 0.0,
+                }
+            }
+            Self::Nut {
+                ups,
+                host,
+                port,
+                interval,
+                poller,
+                warning,
+            } => {
+                let poller = poller.get_or_insert_with(|| {
+                    let (ups, host, port) = (ups.clone(), host.clone(), *port);
+                    let interval = Duration::from_secs_f32(*interval);
+                    Poller::spawn(interval, move || {
+                        nut::query(&host, port, &ups, NUT_TIMEOUT)
+                    })
+                });
+                match poller.latest() {
+                    Some(Ok(state)) if state.low_battery => 1.0,
+                    Some(Ok(state)) if state.on_battery => {
+                        (1.0 - state.charge / 100.0).clamp(0.0, 1.0)
+                    }
+                    Some(Ok(_)) | None => 0.0,
+                    Some(Err(err)) => {
+                        warning.warn(format_args!(
+                            "Couldn't ask upsd on {host} about {ups}: {err}"
+                        ));
+                        1.0
+                    }
+                }
+            }
+            Self::Vpn {
+                interface,
+                stale_after,
+                poller,
+            } => {
+                let poller = poller.get_or_insert_with(|| {
+                    let interface = interface.clone();
+                    let stale_after = Duration::from_secs_f32(*stale_after);
+                    Poller::spawn(VPN_INTERVAL, move || {
+                        vpn::status(&interface, stale_after)
+                    })
+                });
+                poller.latest().copied().unwrap_or(0.0)
+            }
+            Self::WifiSignal { min_dbm, max_dbm } => procfs::wifi_signal_dbm()
+                .map_or(0.0, |dbm| {
+                    ((dbm - *min_dbm) / (*max_dbm - *min_dbm)).clamp(0.0, 1.0)
+                }),
+            Self::NetworkErrorRate { ceiling, counter } => {
+                // Drops aren't available everywhere; errors alone still
+                // say something.
+                let drops = procfs::network_drops().unwrap_or(0);
+                let total =
+                    system.get_network_total_errors().wrapping_add(drops);
+                (counter.rate(total) / *ceiling).clamp(0.0, 1.0)
+            }
+            Self::NetworkThroughput {
+                capacity,
+                interface,
+                counter,
+                warning,
+            } => {
+                let interface = interface.as_deref();
+                if !has_interface(system, interface, warning) {
+                    return 0.0;
+                }
+                let bytes_per_second =
+                    counter.rate(system.get_network_total_bytes(interface));
+                let capacity_bytes = *capacity * 1_000_000.0 / 8.0;
+                (bytes_per_second / capacity_bytes).clamp(0.0, 1.0)
+            }
+            Self::Smart {
+                devices,
+                max_sectors,
+                interval,
+                poller,
+                warning,
+            } => {
+                let poller = poller.get_or_insert_with(|| {
+                    let devices = devices.clone();
+                    let interval = Duration::from_secs_f32(*interval);
+                    Poller::spawn(interval, move || {
+                        devices
+                            .iter()
+                            .map(|device| smart::bad_sectors(device))
+                            .collect()
+                    })
+                });
+                smart_alarm(poller.latest(), *max_sectors, warning)
+            }
+            Self::Mdraid { poller } => {
+                let poller = poller.get_or_insert_with(|| {
+                    Poller::spawn(MDSTAT_INTERVAL, procfs::mdraid_state)
+                });
+                poller.latest().copied().flatten().unwrap_or(0.0)
+            }
+            Self::DiskUsage {
+                path,
+                poller,
+                warning,
+            } => filesystem_usage(path, poller, warning, || {
+                let mut system = System::new();
+                move |path: &Path| {
+                    system.refresh_disks_list();
+                    system.get_disk_usage_percent(path)
+                }
+            }),
+            Self::FileAge { path, max_hours } => filesystem::age(path)
+                .map_or(1.0, |age| {
+                    (age.as_secs_f32() / (*max_hours * 3600.0)).min(1.0)
+                }),
+            Self::InodeUsage {
+                path,
+                poller,
+                warning,
+            } => filesystem_usage(path, poller, warning, || {
+                filesystem::inode_usage
+            }),
+            Self::DiskIo { ceiling, counter } => procfs::disk_io_bytes()
+                .map_or(0.0, |bytes| {
+                    (counter.rate(bytes) / *ceiling).clamp(0.0, 1.0)
+                }),
+            Self::GpuUsage { device } => {
+                gpu::usage_percent(*device).unwrap_or(0.0)
+            }
+            Self::GpuMemory { device } => gpu::memory_percent(*device)
+                .map_or(0.0, |fraction| fraction.clamp(0.0, 1.0)),
+            Self::GpuTemperature { max, device } => system
+                .get_gpu_temperature()
+                .or_else(|| gpu::temperature(*device))
+                .map_or(0.0, |temperature| {
+                    (temperature / *max).clamp(0.0, 1.0)
+                }),
+            Self::Battery => power::battery_percent().unwrap_or(0.0),
+            Self::BatteryDischarge { max_watts } => {
+                (power::discharge_watts() / *max_watts).clamp(0.0, 1.0)
+            }
+            Self::Charging => {
+                if power::on_external_power() {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            #[allow(clippy::cast_precision_loss)]
+            Self::FanSpeed { max_rpm, aggregate } => {
+                aggregate.apply(hwmon::fans().iter().map(|fan| {
+                    let max = fan.max.map_or(*max_rpm, |max| max as f32);
+                    (fan.rpm as f32 / max).clamp(0.0, 1.0)
+                }))
+            }
+            Self::Ping {
+                host,
+                max_ms,
+                poller,
+            } => {
+                let poller = poller.get_or_insert_with(|| {
+                    let host = host.clone();
+                    let mut warning = WarnOnce::default();
+                    Poller::spawn(PING_INTERVAL, move || {
+                        probe::ping(&host, PING_TIMEOUT, &mut warning)
+                    })
+                });
+                match poller.latest() {
+                    Some(Some(latency)) => (latency.as_secs_f32() * 1000.0
+                        / *max_ms)
+                        .clamp(0.0, 1.0),
+                    Some(None) => 1.0,
+                    None => 0.0,
+                }
+            }
+            Self::Http {
+                url,
+                timeout,
+                interval,
+                poller,
+            } => {
+                let timeout = Duration::from_secs_f32(*timeout);
+                let poller = poller.get_or_insert_with(|| {
+                    let url = url.clone();
+                    let interval = Duration::from_secs_f32(*interval);
+                    Poller::spawn(interval, move || probe::http(&url, timeout))
+                });
+                match poller.latest() {
+                    Some(Some(elapsed)) => (elapsed.as_secs_f32()
+                        / timeout.as_secs_f32())
+                    .clamp(0.0, 1.0),
+                    Some(None) => 1.0,
+                    None => 0.0,
+                }
+            }
+            Self::Dns {
+                host,
+                server,
+                max_ms,
+                interval,
+                poller,
+            } => {
+                let poller = poller.get_or_insert_with(|| {
+                    let (host, server) = (host.clone(), server.clone());
+                    let interval = Duration::from_secs_f32(*interval);
+                    // Leaves the poller time to sleep between lookups even
+                    // when they all time out.
+                    let timeout = interval / 2;
+                    Poller::spawn(interval, move || {
+                        probe::dns(&host, server.as_deref(), timeout)
+                    })
+                });
+                match poller.latest() {
+                    Some(Some(elapsed)) => (elapsed.as_secs_f32() * 1000.0
+                        / *max_ms)
+                        .clamp(0.0, 1.0),
+                    Some(None) => 1.0,
+                    None => 0.0,
+                }
+            }
+            Self::ClockOffset { max_ms, poller } => {
+                let poller = poller.get_or_insert_with(|| {
+                    Poller::spawn(CLOCK_OFFSET_INTERVAL, || {
+                        timesync::offset_ms(CLOCK_OFFSET_TIMEOUT)
+                    })
+                });
+                match poller.latest() {
+                    Some(Some(offset)) => (offset / *max_ms).clamp(0.0, 1.0),
+                    Some(None) => 1.0,
+                    None => 0.0,
+                }
+            }
+            #[allow(clippy::cast_precision_loss)]
+            Self::JournalErrors { ceiling, follower } => {
+                let follower = follower.get_or_insert_with(|| {
+                    Follower::spawn(
+                        JOURNAL_WINDOW,
+                        || systemd::follow_journal("err"),
+                        |_| true,
+                    )
+                });
+                (follower.count() as f32 / *ceiling).clamp(0.0, 1.0)
+            }
+            #[allow(clippy::cast_precision_loss)]
+            Self::JackXruns {
+                log,
+                ceiling,
+                follower,
+            } => {
+                let follower = follower.get_or_insert_with(|| {
+                    // Checked by validate.
+                    let log = log.clone().or_else(jack::default_log).unwrap();
+                    Follower::spawn(
+                        XRUN_WINDOW,
+                        move || jack::follow_log(&log),
+                        jack::is_xrun,
+                    )
+                });
+                (follower.count() as f32 / *ceiling).clamp(0.0, 1.0)
+            }
+            Self::FailedUnits { interval, poller } => {
+                let poller = poller.get_or_insert_with(|| {
+                    let interval = Duration::from_secs_f32(*interval);
+                    Poller::spawn(interval, systemd::failed_units)
+                });
+                match poller.latest() {
+                    Some(Some(0)) | None => 0.0,
+                    Some(_) => 1.0,
+                }
+            }
+            Self::SystemdUnit {
+                unit,
+                interval,
+                poller,
+            } => {
+                let poller = poller.get_or_insert_with(|| {
+                    let unit = unit.clone();
+                    let interval = Duration::from_secs_f32(*interval);
+                    Poller::spawn(interval, move || systemd::unit_state(&unit))
+                });
+                poller.latest().copied().unwrap_or(0.0)
+            }
+            #[allow(clippy::cast_precision_loss)]
+            Self::Updates {
+                backend,
+                ceiling,
+                interval,
+                poller,
+                value,
+            } => {
+                let poller = poller.get_or_insert_with(|| {
+                    let backend = *backend;
+                    let interval = Duration::from_secs_f32(*interval);
+                    Poller::spawn(interval, move || {
+                        updates::pending(backend, UPDATES_TIMEOUT)
+                    })
+                });
+                if let Some(&Some(pending)) = poller.latest() {
+                    *value = (pending as f32 / *ceiling as f32).min(1.0);
+                }
+                *value
+            }
+            Self::Command {
+                command,
+                interval,
+                timeout,
+                scale,
+                poller,
+                value,
+            } => {
+                let poller = poller.get_or_insert_with(|| {
+                    let command = command.clone();
+                    let interval = Duration::from_secs_f32(*interval);
+                    let timeout = Duration::from_secs_f32(*timeout);
+                    let scale = *scale;
+                    Poller::spawn(interval, move || {
+                        command::run(&command, timeout)?
+                            .trim()
+                            .parse::<f32>()
+                            .ok()
+                            .filter(|value| value.is_finite())
+                            .map(|value| (value / scale).clamp(0.0, 1.0))
+                    })
+                });
+                if let Some(&Some(latest)) = poller.latest() {
+                    *value = latest;
+                }
+                *value
+            }
+            #[allow(clippy::cast_precision_loss)]
+            Self::Docker {
+                host,
+                expected,
+                unreachable,
+                poller,
+            } => {
+                let poller = poller.get_or_insert_with(|| {
+                    let host = host.clone();
+                    Poller::spawn(DOCKER_INTERVAL, move || {
+                        docker::running_containers(&host)
+                    })
+                });
+                match poller.latest() {
+                    Some(Some(running)) => (1.0
+                        - *running as f32 / *expected as f32)
+                        .clamp(0.0, 1.0),
+                    Some(None) => *unreachable,
+                    None => 0.0,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} isn't {}",
+            actual,
+            expected
+        );
+    }
+
+    /// A counter that last read `value` `ago`.
+    fn counter_at(value: u64, ago: Duration) -> Counter {
+        Counter {
+            previous: Some((value, Instant::now().checked_sub(ago).unwrap())),
+        }
+    }
+
+    #[test]
+    fn first_counter_sample_reads_as_zero() {
+        let mut counter = Counter::default();
+        assert_near(counter.rate(1_000_000), 0.0, 0.0);
+    }
+
+    #[test]
+    fn steady_counter_growth_reads_as_its_rate() {
+        for (previous, current) in [(0, 1000), (1000, 2000), (2000, 3000)] {
+            let mut counter = counter_at(previous, Duration::from_secs(2));
+            assert_near(counter.rate(current), 500.0, 5.0);
+        }
+    }
+
+    #[test]
+    fn counter_reset_reads_as_zero() {
+        let mut counter = counter_at(5000, Duration::from_secs(1));
+        assert_near(counter.rate(10), 0.0, 0.0);
+        // Rates pick up again from the new baseline.
+        assert_eq!(counter.previous.map(|(value, _)| value), Some(10));
+        let mut counter = counter_at(10, Duration::from_secs(1));
+        assert_near(counter.rate(110), 100.0, 1.0);
+    }
+
+    #[test]
+    fn wrapping_counter_counts_across_the_wrap() {
+        let mut counter = counter_at(250, Duration::from_secs(1));
+        assert_near(counter.wrapping_rate(50, 256), 56.0, 1.0);
+    }
+
+    #[test]
+    fn latch_ignores_kills_from_before_the_first_sample() {
+        let mut latch = Latch::default();
+        let hold = Duration::from_mins(10);
+        assert_near(latch.update(Some(3), hold), 0.0, 0.0);
+        assert_near(latch.update(Some(3), hold), 0.0, 0.0);
+        assert_eq!(latch.events(), 0);
+    }
+
+    #[test]
+    fn latch_holds_after_a_kill() {
+        let mut latch = Latch::default();
+        let hold = Duration::from_mins(10);
+        latch.update(Some(3), hold);
+        assert_near(latch.update(Some(4), hold), 1.0, 0.0);
+        // Unreadable samples in between don't clear it.
+        assert_near(latch.update(None, hold), 1.0, 0.0);
+        assert_near(latch.update(Some(4), hold), 1.0, 0.0);
+        assert_eq!(latch.events(), 1);
+    }
+
+    #[test]
+    fn latch_stays_dark_without_a_counter() {
+        // Kernels before 4.13 have no `oom_kill` to read.
+        let mut latch = Latch::default();
+        let hold = Duration::from_mins(10);
+        for _ in 0..3 {
+            assert_near(latch.update(None, hold), 0.0, 0.0);
+        }
+    }
+
+    fn validate(config: &str) -> Result<(), String> {
+        toml::from_str::<Metric>(config).unwrap().validate()
+    }
+
+    #[test]
+    fn settings_that_arent_numbers_are_rejected() {
+        for config in [
+            "metric = \"http\"\nurl = \"http://localhost\"\ntimeout = nan",
+            "metric = \"http\"\nurl = \"http://localhost\"\ninterval = nan",
+            "metric = \"http\"\nurl = \"http://localhost\"\ntimeout = inf",
+            "metric = \"dns\"\nhost = \"example.com\"\ninterval = inf",
+            "metric = \"ping\"\nhost = \"example.com\"\nmax_ms = nan",
+            "metric = \"disk_io\"\nceiling = nan",
+            "metric = \"command\"\ncommand = \"true\"\ninterval = nan",
+            "metric = \"throttling\"\nhold = nan",
+            "metric = \"wifi_signal\"\nmin_dbm = nan",
+        ] {
+            assert!(validate(config).is_err(), "{}", config);
+        }
+    }
+
+    #[test]
+    fn http_timeout_cant_outlast_its_interval() {
+        let http = "metric = \"http\"\nurl = \"http://localhost\"";
+        assert!(
+            validate(&format!("{http}\ntimeout = 2\ninterval = 5")).is_ok()
+        );
+        assert!(
+            validate(&format!("{http}\ntimeout = 5\ninterval = 5")).is_ok()
+        );
+        assert!(
+            validate(&format!("{http}\ntimeout = 6\ninterval = 5")).is_err()
+        );
+    }
+}
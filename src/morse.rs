@@ -0,0 +1,95 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Turning text into Morse code blinks.
+use std::time::Duration;
+
+/// One stretch of a pattern: the pad lit or dark for `length`.
+#[derive(Clone, Copy)]
+pub struct Segment {
+    pub lit: bool,
+    pub length: Duration,
+}
+
+/// Dots and dashes of `letter`, or `None` if Morse code doesn't have it.
+const fn code(letter: char) -> Option<&'static str> {
+    Some(match letter.to_ascii_uppercase() {
+        'A' => ".-",
+        'B' => "-...",
+        'C' => "-.-.",
+        'D' => "-..",
+        'E' => ".",
+        'F' => "..-.",
+        'G' => "--.",
+        'H' => "....",
+        'I' => "..",
+        'J' => ".---",
+        'K' => "-.-",
+        'L' => ".-..",
+        'M' => "--",
+        'N' => "-.",
+        'O' => "---",
+        'P' => ".--.",
+        'Q' => "--.-",
+        'R' => ".-.",
+        'S' => "...",
+        'T' => "-",
+        'U' => "..-",
+        'V' => "...-",
+        'W' => ".--",
+        'X' => "-..-",
+        'Y' => "-.--",
+        'Z' => "--..",
+        '0' => "-----",
+        '1' => ".----",
+        '2' => "..---",
+        '3' => "...--",
+        '4' => "....-",
+        '5' => ".....",
+        '6' => "-....",
+        '7' => "--...",
+        '8' => "---..",
+        '9' => "----.",
+        _ => return None,
+    })
+}
+
+/// The blinks spelling out `text`, with the usual timing: a dash is three
+/// dots long, and the gaps are a dot within a letter, three between
+/// letters and seven between words. The pattern ends with a word gap, so
+/// it can be looped.
+pub fn compile(text: &str, dot: Duration) -> Result<Vec<Segment>, String> {
+    let mut segments = vec![];
+    let gap = |segments: &mut Vec<Segment>, dots: u32| {
+        // Widens the gap after the last symbol rather than adding another.
+        if let Some(last) = segments.last_mut() {
+            last.length = dot * dots;
+        }
+    };
+    for word in text.split_whitespace() {
+        for letter in word.chars() {
+            let code = code(letter).ok_or_else(|| {
+                format!("{letter:?} can't be written in Morse code")
+            })?;
+            for symbol in code.chars() {
+                let dots = if symbol == '-' { 3 } else { 1 };
+                segments.push(Segment {
+                    lit: true,
+                    length: dot * dots,
+                });
+                segments.push(Segment {
+                    lit: false,
+                    length: dot,
+                });
+            }
+            gap(&mut segments, 3);
+        }
+        gap(&mut segments, 7);
+    }
+    if segments.is_empty() {
+        return Err("there's nothing to write in Morse code".into());
+    }
+    Ok(segments)
+}
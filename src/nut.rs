@@ -0,0 +1,131 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! A minimal client for `upsd`, the Network UPS Tools daemon.
+//!
+//! Only speaks enough of the line-based protocol to read variables: each
+//! `GET VAR <ups> <name>` is answered with `VAR <ups> <name> "<value>"`,
+//! or `ERR <reason>`.
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// What the UPS reported.
+#[derive(Debug, Clone, Copy)]
+pub struct UpsState {
+    /// Battery charge, from 0 to 100.
+    pub charge: f32,
+    pub on_battery: bool,
+    pub low_battery: bool,
+}
+
+/// Connects to `upsd` on `host:port` and asks for the state of `ups`.
+pub fn query(
+    host: &str,
+    port: u16,
+    ups: &str,
+    timeout: Duration,
+) -> io::Result<UpsState> {
+    let address = (host, port).to_socket_addrs()?.next().ok_or_else(|| {
+        io::Error::new(ErrorKind::NotFound, format!("{host} didn't resolve"))
+    })?;
+    let stream = TcpStream::connect_timeout(&address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut get = |name: &str| -> io::Result<String> {
+        writeln!(writer, "GET VAR {ups} {name}")?;
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        parse_var(&line, ups, name)
+    };
+    let charge = get("battery.charge")?;
+    let status = get("ups.status")?;
+    let charge = charge.parse().map_err(|_| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("battery.charge isn't a number: {charge}"),
+        )
+    })?;
+    // Politeness; upsd copes with clients that hang up without it.
+    let _ = writeln!(writer, "LOGOUT");
+    let flags: Vec<&str> = status.split_whitespace().collect();
+    Ok(UpsState {
+        charge,
+        on_battery: flags.contains(&"OB"),
+        low_battery: flags.contains(&"LB"),
+    })
+}
+
+/// Picks the value out of an answer to `GET VAR <ups> <name>`.
+fn parse_var(line: &str, ups: &str, name: &str) -> io::Result<String> {
+    let line = line.trim_end();
+    if let Some(reason) = line.strip_prefix("ERR ") {
+        return Err(io::Error::other(format!(
+            "upsd couldn't get {name} of {ups}: {reason}"
+        )));
+    }
+    line.strip_prefix("VAR ")
+        .and_then(|rest| rest.strip_prefix(ups))
+        .and_then(|rest| rest.strip_prefix(' '))
+        .and_then(|rest| rest.strip_prefix(name))
+        .and_then(|rest| rest.strip_prefix(" \""))
+        .and_then(|rest| rest.strip_suffix('"'))
+        .map(|value| value.replace("\\\"", "\"").replace("\\\\", "\\"))
+        .ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("unexpected answer from upsd: {line}"),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_value_of_a_var() {
+        let value = parse_var(
+            "VAR ups battery.charge \"87\"\n",
+            "ups",
+            "battery.charge",
+        );
+        assert_eq!(value.unwrap(), "87");
+    }
+
+    #[test]
+    fn unescapes_quoted_values() {
+        let value = parse_var(
+            r#"VAR ups ups.status "OL \"CHRG\" \\""#,
+            "ups",
+            "ups.status",
+        );
+        assert_eq!(value.unwrap(), r#"OL "CHRG" \"#);
+    }
+
+    #[test]
+    fn errors_from_upsd_are_reported() {
+        let error = parse_var("ERR UNKNOWN-UPS\n", "ups", "battery.charge")
+            .unwrap_err();
+        assert!(error.to_string().contains("UNKNOWN-UPS"));
+    }
+
+    #[test]
+    fn malformed_answers_are_invalid_data() {
+        for line in [
+            "",
+            "VAR ups battery.charge 87",
+            "VAR ups battery.charge \"87",
+            "VAR other battery.charge \"87\"",
+            "VAR ups battery.runtime \"1200\"",
+            "BEGIN LIST VAR ups",
+        ] {
+            let error = parse_var(line, "ups", "battery.charge").unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{line}");
+        }
+    }
+}
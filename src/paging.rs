@@ -0,0 +1,135 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Switching every pad between pages of metrics, in turn or when asked.
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// How long every pad lights up for between one page and the next.
+const SWITCH_BLINK: Duration = Duration::from_millis(300);
+
+/// The page picked with `--page` or a signal, counting from 1, or
+/// [`AUTOMATIC`] to go through them in turn.
+static SELECTED: AtomicUsize = AtomicUsize::new(AUTOMATIC);
+
+const AUTOMATIC: usize = 0;
+
+/// Index of the page on the pads, or [`SWITCHING`].
+static SHOWING: AtomicUsize = AtomicUsize::new(0);
+
+/// While the pads blink for a switch, and no page is on them.
+const SWITCHING: usize = usize::MAX;
+
+/// How many switches there have been so far.
+static SWITCHES: AtomicUsize = AtomicUsize::new(0);
+
+/// Keeps page `page`, counting from 1, on the pads until told otherwise.
+pub fn select(page: usize) { SELECTED.store(page, Ordering::Relaxed); }
+
+/// The real-time signal `SIGRTMIN + n` selects page `n`, and `SIGRTMIN`
+/// itself goes back to switching automatically.
+#[cfg(target_os = "linux")]
+static FIRST_SIGNAL: std::sync::atomic::AtomicI32 =
+    std::sync::atomic::AtomicI32::new(0);
+
+#[cfg(target_os = "linux")]
+extern "C" fn on_select(signal: libc::c_int) {
+    let page = signal - FIRST_SIGNAL.load(Ordering::Relaxed);
+    if let Ok(page) = usize::try_from(page) {
+        SELECTED.store(page, Ordering::Relaxed);
+    }
+}
+
+/// Selects page N of `pages` whenever the process gets `SIGRTMIN+N`, e.g.
+/// from `pkill -RTMIN+2 lpd8_monitor`, and goes back to switching
+/// automatically on `SIGRTMIN`.
+#[cfg(target_os = "linux")]
+pub fn listen_for_selections(pages: usize) {
+    let first = libc::SIGRTMIN();
+    FIRST_SIGNAL.store(first, Ordering::Relaxed);
+    let last = libc::c_int::try_from(pages).map_or(first, |n| first + n);
+    for signal in first..=last.min(libc::SIGRTMAX()) {
+        // SAFETY: the handler only touches atomics, which is
+        // async-signal-safe.
+        unsafe {
+            libc::signal(signal, on_select as *const () as libc::sighandler_t);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub const fn listen_for_selections(_pages: usize) {}
+
+/// Whether page `page` is on the pads right now.
+pub fn showing(page: usize) -> bool { SHOWING.load(Ordering::Relaxed) == page }
+
+/// How many times the pads have switched pages so far.
+pub fn switches() -> usize { SWITCHES.load(Ordering::Relaxed) }
+
+/// What the pads are up to.
+pub enum Turn {
+    /// Lit up, between two pages.
+    Blink,
+    /// Showing this page.
+    Page(usize),
+}
+
+/// Which page is up, for the sampler.
+pub struct Paging {
+    pages: usize,
+    /// How long each page stays up while switching automatically.
+    interval: Duration,
+    current: usize,
+    since: Instant,
+    /// When the blink for a switch to `current` ends.
+    blink_until: Option<Instant>,
+}
+
+impl Paging {
+    pub fn new(pages: usize, interval: Duration) -> Self {
+        Self {
+            pages,
+            interval,
+            current: 0,
+            since: Instant::now(),
+            blink_until: None,
+        }
+    }
+
+    /// Goes on to the next page once it's time, or to the selected one.
+    pub fn update(&mut self) -> Turn {
+        let now = Instant::now();
+        if let Some(until) = self.blink_until {
+            if now < until {
+                return Turn::Blink;
+            }
+            self.blink_until = None;
+            SHOWING.store(self.current, Ordering::Relaxed);
+            SWITCHES.fetch_add(1, Ordering::Relaxed);
+            return Turn::Page(self.current);
+        }
+        let next = match SELECTED.load(Ordering::Relaxed) {
+            AUTOMATIC if now.duration_since(self.since) >= self.interval => {
+                (self.current + 1) % self.pages
+            }
+            AUTOMATIC => self.current,
+            selected => {
+                // Picking a page holds off switching automatically, and
+                // going back to it gives the page its full time.
+                self.since = now;
+                (selected - 1).min(self.pages - 1)
+            }
+        };
+        if next == self.current {
+            return Turn::Page(self.current);
+        }
+        self.current = next;
+        self.since = now;
+        self.blink_until = Some(now + SWITCH_BLINK);
+        SHOWING.store(SWITCHING, Ordering::Relaxed);
+        Turn::Blink
+    }
+}
@@ -0,0 +1,50 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+/// Runs a slow or potentially blocking check on its own thread, so the
+/// sampler can pick up the latest result without ever waiting on it.
+pub struct Poller<T> {
+    rx: Receiver<T>,
+    latest: Option<T>,
+    updated: Instant,
+}
+
+impl<T: Send + 'static> Poller<T> {
+    /// Calls `poll` right away, and then every `interval`.
+    pub fn spawn(
+        interval: Duration,
+        mut poll: impl FnMut() -> T + Send + 'static,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        spawn(move || {
+            // Stops once the metric owning the receiver is gone.
+            while tx.send(poll()).is_ok() {
+                sleep(interval);
+            }
+        });
+        Self {
+            rx,
+            latest: None,
+            updated: Instant::now(),
+        }
+    }
+
+    /// The most recent result, if there has been one yet.
+    pub fn latest(&mut self) -> Option<&T> {
+        while let Ok(value) = self.rx.try_recv() {
+            self.latest = Some(value);
+            self.updated = Instant::now();
+        }
+        self.latest.as_ref()
+    }
+
+    /// How long ago the latest result arrived, or how long the first one
+    /// has been pending.
+    pub fn age(&self) -> Duration { self.updated.elapsed() }
+}
@@ -0,0 +1,326 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Battery state, read from `/sys/class/power_supply` on Linux.
+//!
+//! Other platforms have no battery as far as this module is concerned.
+
+#[cfg(target_os = "linux")]
+mod sysfs {
+    use std::fs::{read_dir, read_to_string};
+    use std::path::{Path, PathBuf};
+
+    pub fn read_string(supply: &Path, attribute: &str) -> Option<String> {
+        read_to_string(supply.join(attribute))
+            .ok()
+            .map(|value| value.trim().to_string())
+    }
+
+    pub fn read_u64(supply: &Path, attribute: &str) -> Option<u64> {
+        read_string(supply, attribute)?.parse().ok()
+    }
+
+    pub fn read_i64(supply: &Path, attribute: &str) -> Option<i64> {
+        read_string(supply, attribute)?.parse().ok()
+    }
+
+    /// Power supplies of the given type (`Battery`, `Mains`, ...).
+    ///
+    /// Batteries of peripherals like wireless mice also show up here, with
+    /// their scope set to `Device`; those are skipped.
+    pub fn supplies(kind: &str) -> Vec<PathBuf> {
+        read_dir("/sys/class/power_supply")
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|supply| {
+                        read_string(supply, "type").as_deref() == Some(kind)
+                            && read_string(supply, "scope").as_deref()
+                                != Some("Device")
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Full-charge capacity of a battery, in whichever unit it reports it.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy)]
+enum Size {
+    MicrowattHours(u64),
+    MicroampHours(u64),
+}
+
+#[cfg(target_os = "linux")]
+impl Size {
+    const fn value(self) -> u64 {
+        match self {
+            Self::MicrowattHours(size) | Self::MicroampHours(size) => size,
+        }
+    }
+}
+
+/// Charge of all batteries, averaged by their full-charge capacity.
+#[cfg(target_os = "linux")]
+#[allow(clippy::cast_precision_loss)]
+pub fn battery_percent() -> Option<f32> {
+    let batteries: Vec<(f32, Option<Size>)> = sysfs::supplies("Battery")
+        .iter()
+        .filter_map(|battery| {
+            let percent = sysfs::read_u64(battery, "capacity")? as f32;
+            let size = sysfs::read_u64(battery, "energy_full")
+                .map(Size::MicrowattHours)
+                .or_else(|| {
+                    sysfs::read_u64(battery, "charge_full")
+                        .map(Size::MicroampHours)
+                });
+            Some((percent / 100.0, size))
+        })
+        .collect();
+    average_charge(&batteries)
+}
+
+/// The charges of `batteries` weighted by their sizes, or simply averaged
+/// if their sizes can't be compared: when one of them doesn't report its
+/// size, or they don't all report it in the same unit.
+#[cfg(target_os = "linux")]
+#[allow(clippy::cast_precision_loss)]
+fn average_charge(batteries: &[(f32, Option<Size>)]) -> Option<f32> {
+    use std::mem::discriminant;
+
+    if batteries.is_empty() {
+        return None;
+    }
+    let sizes: Option<Vec<Size>> =
+        batteries.iter().map(|&(_, size)| size).collect();
+    let sizes = sizes
+        .filter(|sizes| {
+            sizes
+                .iter()
+                .all(|size| discriminant(size) == discriminant(&sizes[0]))
+        })
+        .map(|sizes| sizes.into_iter().map(Size::value).collect::<Vec<_>>());
+    let total: u64 = sizes.iter().flatten().sum();
+    let average = match sizes {
+        Some(sizes) if total > 0 => {
+            batteries
+                .iter()
+                .zip(sizes)
+                .map(|(&(percent, _), size)| percent * size as f32)
+                .sum::<f32>()
+                / total as f32
+        }
+        _ => {
+            batteries.iter().map(|&(percent, _)| percent).sum::<f32>()
+                / batteries.len() as f32
+        }
+    };
+    Some(average.clamp(0.0, 1.0))
+}
+
+/// Power drawn from all discharging batteries, in watts. 0 while they
+/// charge or there are none.
+#[cfg(target_os = "linux")]
+pub fn discharge_watts() -> f32 {
+    sysfs::supplies("Battery")
+        .iter()
+        .filter(|battery| {
+            sysfs::read_string(battery, "status").as_deref()
+                == Some("Discharging")
+        })
+        .filter_map(|battery| battery_watts(battery))
+        .sum()
+}
+
+/// Power flowing through `battery`, in watts, whichever way.
+#[cfg(target_os = "linux")]
+#[allow(clippy::cast_precision_loss)]
+fn battery_watts(battery: &std::path::Path) -> Option<f32> {
+    // Same split as for the capacity: either power in µW, or current in
+    // µA, which takes the voltage in µV to turn into power. Some firmware
+    // reports them as negative while discharging.
+    let microwatts = sysfs::read_i64(battery, "power_now")
+        .map(|power| power.unsigned_abs() as f32)
+        .or_else(|| {
+            let current = sysfs::read_i64(battery, "current_now")?;
+            let voltage = sysfs::read_i64(battery, "voltage_now")?;
+            Some(
+                current.unsigned_abs() as f32 * voltage.unsigned_abs() as f32
+                    / 1_000_000.0,
+            )
+        })?;
+    Some(microwatts / 1_000_000.0)
+}
+
+/// Whether the machine is running off external power.
+///
+/// Any online adapter counts. Machines that don't expose their adapters
+/// fall back to the batteries' status, and machines without a battery are
+/// always on external power.
+#[cfg(target_os = "linux")]
+pub fn on_external_power() -> bool {
+    let adapters: Vec<_> = ["Mains", "USB"]
+        .iter()
+        .flat_map(|kind| sysfs::supplies(kind))
+        .collect();
+    if adapters
+        .iter()
+        .any(|adapter| sysfs::read_u64(adapter, "online") == Some(1))
+    {
+        return true;
+    }
+    let batteries = sysfs::supplies("Battery");
+    if batteries.is_empty() {
+        return true;
+    }
+    adapters.is_empty()
+        && batteries.iter().all(|battery| {
+            sysfs::read_string(battery, "status").as_deref()
+                != Some("Discharging")
+        })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub const fn battery_percent() -> Option<f32> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn discharge_watts() -> f32 { 0.0 }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn on_external_power() -> bool { true }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    /// A battery directory with the given attributes, like
+    /// `/sys/class/power_supply/BAT0`.
+    fn battery(name: &str, attributes: &[(&str, &str)]) -> PathBuf {
+        let battery = std::env::temp_dir()
+            .join(format!("lpd8-battery-{}-{name}", std::process::id()));
+        fs::create_dir_all(&battery).unwrap();
+        for (attribute, value) in attributes {
+            fs::write(battery.join(attribute), format!("{value}\n")).unwrap();
+        }
+        battery
+    }
+
+    fn assert_watts(battery: &Path, expected: f32) {
+        let watts = battery_watts(battery).unwrap();
+        fs::remove_dir_all(battery).unwrap();
+        assert!(
+            (watts - expected).abs() < 1e-3,
+            "{} isn't {}",
+            watts,
+            expected
+        );
+    }
+
+    fn assert_charge(batteries: &[(f32, Option<Size>)], expected: f32) {
+        let charge = average_charge(batteries).unwrap();
+        assert!(
+            (charge - expected).abs() < 1e-6,
+            "{} isn't {}",
+            charge,
+            expected
+        );
+    }
+
+    #[test]
+    fn charge_is_weighted_by_size() {
+        assert_charge(
+            &[
+                (1.0, Some(Size::MicrowattHours(60_000_000))),
+                (0.0, Some(Size::MicrowattHours(20_000_000))),
+            ],
+            0.75,
+        );
+        assert_charge(
+            &[
+                (0.5, Some(Size::MicroampHours(3_000_000))),
+                (1.0, Some(Size::MicroampHours(1_000_000))),
+            ],
+            0.625,
+        );
+    }
+
+    #[test]
+    fn sizeless_batteries_are_averaged() {
+        assert_charge(
+            &[(1.0, Some(Size::MicrowattHours(60_000_000))), (0.0, None)],
+            0.5,
+        );
+        assert_charge(&[(0.2, None), (0.6, None)], 0.4);
+    }
+
+    #[test]
+    fn mixed_units_are_averaged() {
+        assert_charge(
+            &[
+                (1.0, Some(Size::MicrowattHours(60_000_000))),
+                (0.0, Some(Size::MicroampHours(4_000_000))),
+            ],
+            0.5,
+        );
+    }
+
+    #[test]
+    fn no_batteries_have_no_charge() {
+        assert_eq!(average_charge(&[]), None);
+    }
+
+    #[test]
+    fn reads_power_in_microwatts() {
+        let battery = battery("power", &[("power_now", "12500000")]);
+        assert_watts(&battery, 12.5);
+    }
+
+    #[test]
+    fn multiplies_current_and_voltage() {
+        // 1.25 A at 11.4 V.
+        let battery = battery(
+            "current",
+            &[("current_now", "1250000"), ("voltage_now", "11400000")],
+        );
+        assert_watts(&battery, 14.25);
+    }
+
+    #[test]
+    fn negative_readings_count_as_drawn() {
+        let power = battery("negative-power", &[("power_now", "-8000000")]);
+        assert_watts(&power, 8.0);
+        let current = battery(
+            "negative-current",
+            &[("current_now", "-500000"), ("voltage_now", "12000000")],
+        );
+        assert_watts(&current, 6.0);
+    }
+
+    #[test]
+    fn power_wins_over_current() {
+        let battery = battery(
+            "both",
+            &[
+                ("power_now", "10000000"),
+                ("current_now", "1000000"),
+                ("voltage_now", "12000000"),
+            ],
+        );
+        assert_watts(&battery, 10.0);
+    }
+
+    #[test]
+    fn missing_voltage_reads_as_nothing() {
+        let battery = battery("no-voltage", &[("current_now", "1000000")]);
+        let watts = battery_watts(&battery);
+        fs::remove_dir_all(&battery).unwrap();
+        assert_eq!(watts, None);
+    }
+}
@@ -0,0 +1,219 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Checks against other machines on the network.
+//!
+//! These all block for up to their timeout, so metrics run them through a
+//! `Poller` rather than on the sampler thread.
+use std::io::{ErrorKind, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::command;
+use crate::metric::WarnOnce;
+
+/// Round-trip time to `host`, or `None` if it didn't answer in time.
+///
+/// Sends an ICMP echo with the system's `ping`, which has the privileges
+/// needed for raw sockets. Where there's no `ping`, or it isn't allowed
+/// to send ICMP, falls back to timing a TCP handshake instead, saying so
+/// through `warning`.
+pub fn ping(
+    host: &str,
+    timeout: Duration,
+    warning: &mut WarnOnce,
+) -> Option<Duration> {
+    let seconds = timeout.as_secs().max(1).to_string();
+    let Ok(output) = Command::new("ping")
+        .args(["-n", "-c", "1", "-W", &seconds, host])
+        .output()
+    else {
+        warning.warn(format_args!(
+            "ping isn't installed; timing TCP connections to {host} instead."
+        ));
+        return tcp_ping(host, timeout);
+    };
+    if !output.status.success()
+        && not_permitted(&String::from_utf8_lossy(&output.stderr))
+    {
+        warning.warn(format_args!(
+            "ping isn't allowed to send ICMP; timing TCP connections to \
+             {host} instead."
+        ));
+        return tcp_ping(host, timeout);
+    }
+    parse_ping(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Whether `ping` failed for lack of the privileges to open its socket,
+/// like `ping: socket: Operation not permitted`, rather than because the
+/// host didn't answer.
+fn not_permitted(stderr: &str) -> bool {
+    let stderr = stderr.to_ascii_lowercase();
+    stderr.contains("operation not permitted")
+        || stderr.contains("permission denied")
+}
+
+/// Picks the `time=12.3 ms` out of `ping`'s output.
+fn parse_ping(output: &str) -> Option<Duration> {
+    let time = output.split_whitespace().find_map(|word| {
+        word.strip_prefix("time=")
+            .or_else(|| word.strip_prefix("time<"))
+    })?;
+    let millis: f64 = time.trim_end_matches("ms").parse().ok()?;
+    Some(Duration::from_secs_f64(millis / 1000.0))
+}
+
+/// Time until `host` answers a TCP connection on port 80. A refused
+/// connection still means the host answered, so that counts too.
+fn tcp_ping(host: &str, timeout: Duration) -> Option<Duration> {
+    let address = (host, 80).to_socket_addrs().ok()?.next()?;
+    let start = Instant::now();
+    match TcpStream::connect_timeout(&address, timeout) {
+        Ok(_) => Some(start.elapsed()),
+        Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+            Some(start.elapsed())
+        }
+        Err(_) => None,
+    }
+}
+
+/// How long a GET of `url` took, or `None` if it failed, timed out or the
+/// server answered with an error status.
+///
+/// Uses `curl`, so TLS and redirects come for free.
+pub fn http(url: &str, timeout: Duration) -> Option<Duration> {
+    let output = Command::new("curl")
+        .args(["--silent", "--location", "--output", "/dev/null"])
+        .args(["--write-out", "%{http_code} %{time_total}"])
+        .arg("--max-time")
+        .arg(timeout.as_secs_f32().to_string())
+        .arg(url)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8_lossy(&output.stdout);
+    let mut fields = output.split_whitespace();
+    let status: u16 = fields.next()?.parse().ok()?;
+    let seconds: f64 = fields.next()?.parse().ok()?;
+    if status >= 400 {
+        return None;
+    }
+    Some(Duration::from_secs_f64(seconds))
+}
+
+/// How long it took to look up `host`, or `None` if it doesn't exist or
+/// the lookup took longer than `timeout`.
+///
+/// Asks the system resolver through `getent`, so `/etc/hosts` and
+/// whatever else NSS is set up with count too, or `server` directly with
+/// `dig`. Either way, starting the program is part of the time, but that's
+/// only a few milliseconds.
+pub fn dns(
+    host: &str,
+    server: Option<&str>,
+    timeout: Duration,
+) -> Option<Duration> {
+    let mut command = server.map_or_else(
+        || {
+            let mut getent = Command::new("getent");
+            getent.args(["ahosts", host]);
+            getent
+        },
+        |server| {
+            let mut dig = Command::new("dig");
+            let seconds = timeout.as_secs().max(1);
+            dig.args(["+short", "+tries=1", &format!("+time={seconds}")])
+                .arg(format!("@{server}"))
+                .arg(host);
+            dig
+        },
+    );
+    let start = Instant::now();
+    let output = command::output(&mut command, timeout)?;
+    // dig succeeds on NXDOMAIN too, it just doesn't print any addresses.
+    if output.trim().is_empty() {
+        return None;
+    }
+    Some(start.elapsed())
+}
+
+/// Time left until the certificate `host` presents on `port` expires, 0 if
+/// it already has, or `None` if it couldn't be fetched.
+///
+/// Uses the `openssl` command line tool, both to do the handshake and to
+/// read the certificate.
+pub fn tls_expiry(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> Option<Duration> {
+    let certificate = command::output(
+        Command::new("openssl")
+            .args(["s_client", "-connect", &format!("{host}:{port}")])
+            .args(["-servername", host]),
+        timeout,
+    )?;
+    let mut x509 = Command::new("openssl")
+        .args(["x509", "-noout", "-enddate"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    x509.stdin.take()?.write_all(certificate.as_bytes()).ok()?;
+    let output = x509.wait_with_output().ok()?;
+    let end = String::from_utf8_lossy(&output.stdout);
+    let not_after = parse_openssl_date(end.trim().strip_prefix("notAfter=")?)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    Some(Duration::from_secs(not_after).saturating_sub(now))
+}
+
+/// Turns a date like `Jun  1 12:00:00 2025 GMT` into seconds since the
+/// epoch.
+fn parse_openssl_date(date: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct",
+        "Nov", "Dec",
+    ];
+    let mut fields = date.split_whitespace();
+    let month = fields.next()?;
+    let (month, _) = (1u64..).zip(MONTHS).find(|&(_, name)| name == month)?;
+    let day: u64 = fields.next()?.parse().ok()?;
+    let mut time = fields.next()?.split(':').map(str::parse::<u64>);
+    let (hours, minutes, seconds) =
+        (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    let year: u64 = fields.next()?.parse().ok()?;
+    // Days since 1970-01-01, counting years from March so that the leap
+    // day comes last (Howard Hinnant's days_from_civil).
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era =
+        year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+    Some(days * 86_400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_missing_icmp_privileges() {
+        assert!(not_permitted("ping: socket: Operation not permitted\n"));
+        assert!(not_permitted("ping: icmp open socket: Permission denied\n"));
+        assert!(!not_permitted("ping: unknown host example.invalid\n"));
+        assert!(!not_permitted(""));
+    }
+}
@@ -0,0 +1,616 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Readers for Linux-specific counters that `sysinfo` doesn't expose.
+//!
+//! Everything here returns `None` on other platforms, or if the file is
+//! missing or unreadable.
+use std::convert::TryFrom;
+
+use serde::Deserialize;
+
+/// Time all CPUs together have spent in various states since boot, in
+/// clock ticks.
+#[derive(Clone, Copy)]
+pub struct CpuTimes {
+    pub iowait: u64,
+    /// Time a hypervisor ran something else while this machine's CPUs
+    /// were ready to run.
+    pub steal: u64,
+    pub total: u64,
+}
+
+/// The aggregate `cpu` line of `/proc/stat`.
+#[cfg(target_os = "linux")]
+pub fn cpu_times() -> Option<CpuTimes> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    let times = line
+        .split_whitespace()
+        .skip(1)
+        .map(str::parse)
+        .collect::<Result<Vec<u64>, _>>()
+        .ok()?;
+    // user nice system idle iowait irq softirq steal guest guest_nice; the
+    // guest times are already included in user and nice.
+    Some(CpuTimes {
+        iowait: *times.get(4)?,
+        steal: *times.get(7)?,
+        total: times.iter().take(8).sum(),
+    })
+}
+
+/// Context switches across all CPUs since boot.
+#[cfg(target_os = "linux")]
+pub fn context_switches() -> Option<u64> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    stat.lines()
+        .find_map(|line| line.strip_prefix("ctxt "))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Thermal throttling events of all cores and packages since boot.
+///
+/// Every core reports the count of its package too, so package events are
+/// counted several times; that's fine for noticing new ones.
+#[cfg(target_os = "linux")]
+pub fn throttle_events() -> Option<u64> {
+    use std::fs::{read_dir, read_to_string};
+
+    let mut found = false;
+    let mut total = 0u64;
+    for cpu in read_dir("/sys/devices/system/cpu")
+        .ok()?
+        .filter_map(Result::ok)
+    {
+        let throttle = cpu.path().join("thermal_throttle");
+        for counter in ["core_throttle_count", "package_throttle_count"] {
+            let count = read_to_string(throttle.join(counter))
+                .ok()
+                .and_then(|count| count.trim().parse::<u64>().ok());
+            if let Some(count) = count {
+                found = true;
+                total = total.wrapping_add(count);
+            }
+        }
+    }
+    found.then_some(total)
+}
+
+/// The counter `name` from `/proc/vmstat`. Which counters exist, and in
+/// what order, depends on the kernel version.
+#[cfg(target_os = "linux")]
+pub fn vmstat(name: &str) -> Option<u64> {
+    parse_vmstat(&std::fs::read_to_string("/proc/vmstat").ok()?, name)
+}
+
+#[cfg(target_os = "linux")]
+fn parse_vmstat(vmstat: &str, name: &str) -> Option<u64> {
+    vmstat.lines().find_map(|line| {
+        let (key, value) = line.split_once(' ')?;
+        if key == name {
+            value.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// Processes killed by the OOM killer since boot. Kernels before 4.13
+/// don't count them.
+pub fn oom_kills() -> Option<u64> { vmstat("oom_kill") }
+
+/// Pages swapped in and out since boot.
+pub fn swap_pages() -> Option<u64> {
+    Some(vmstat("pswpin")?.wrapping_add(vmstat("pswpout")?))
+}
+
+/// Size of a memory page, in bytes.
+#[cfg(target_os = "linux")]
+pub fn page_size() -> u64 {
+    // SAFETY: sysconf has no preconditions.
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    u64::try_from(size).unwrap_or(4096)
+}
+
+/// Page faults that had to wait for the disk since boot.
+pub fn major_page_faults() -> Option<u64> { vmstat("pgmajfault") }
+
+/// Interrupts serviced since boot.
+#[cfg(target_os = "linux")]
+pub fn interrupts() -> Option<u64> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    // The total comes first, followed by a count for every IRQ number;
+    // those go into the thousands and aren't needed.
+    let intr = stat.lines().find_map(|line| line.strip_prefix("intr "))?;
+    intr.split(' ').next()?.parse().ok()
+}
+
+/// Bytes read from and written to physical block devices since boot.
+#[cfg(target_os = "linux")]
+pub fn disk_io_bytes() -> Option<u64> {
+    use std::fs::read_to_string;
+    use std::path::Path;
+
+    // /proc/diskstats always counts in 512-byte sectors, regardless of the
+    // device's actual sector size.
+    const SECTOR_SIZE: u64 = 512;
+    let diskstats = read_to_string("/proc/diskstats").ok()?;
+    let mut total = 0u64;
+    for line in diskstats.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 {
+            continue;
+        }
+        // Partitions, loop devices and device-mapper targets would count
+        // the same I/O more than once, so only look at devices that are
+        // backed by actual hardware.
+        let name = fields[2];
+        if !Path::new("/sys/block").join(name).join("device").exists() {
+            continue;
+        }
+        let read: u64 = fields[5].parse().ok()?;
+        let written: u64 = fields[9].parse().ok()?;
+        total = total.wrapping_add((read + written) * SECTOR_SIZE);
+    }
+    Some(total)
+}
+
+/// Available entropy and pool size of the kernel's random number
+/// generator, in bits.
+#[cfg(target_os = "linux")]
+pub fn entropy() -> Option<(u64, u64)> {
+    let read = |name: &str| {
+        std::fs::read_to_string(format!("/proc/sys/kernel/random/{name}"))
+            .ok()?
+            .trim()
+            .parse::<u64>()
+            .ok()
+    };
+    Some((read("entropy_avail")?, read("poolsize")?))
+}
+
+/// Size of the ZFS adaptive replacement cache and its ceiling, in bytes.
+#[derive(Clone, Copy)]
+pub struct ArcStats {
+    pub size: u64,
+    pub c_max: u64,
+}
+
+/// The ARC statistics, if the ZFS module is loaded.
+#[cfg(target_os = "linux")]
+pub fn arcstats() -> Option<ArcStats> {
+    let stats =
+        std::fs::read_to_string("/proc/spl/kstat/zfs/arcstats").ok()?;
+    // After two header lines, each line is `name type data`.
+    let field = |name: &str| {
+        stats.lines().find_map(|line| {
+            let mut fields = line.split_whitespace();
+            if fields.next()? != name {
+                return None;
+            }
+            fields.nth(1)?.parse::<u64>().ok()
+        })
+    };
+    Some(ArcStats {
+        size: field("size")?,
+        c_max: field("c_max")?,
+    })
+}
+
+/// Packets dropped on receive and transmit by all interfaces since boot.
+#[cfg(target_os = "linux")]
+pub fn network_drops() -> Option<u64> {
+    let dev = std::fs::read_to_string("/proc/net/dev").ok()?;
+    let mut total = 0u64;
+    // Two header lines, then `name: <8 receive fields> <8 transmit fields>`
+    // with the drop count fourth in each group.
+    for line in dev.lines().skip(2) {
+        let (_, counters) = line.split_once(':')?;
+        let fields: Vec<&str> = counters.split_whitespace().collect();
+        let received: u64 = fields.get(3)?.parse().ok()?;
+        let transmitted: u64 = fields.get(11)?.parse().ok()?;
+        total = total.wrapping_add(received).wrapping_add(transmitted);
+    }
+    Some(total)
+}
+
+/// Strongest signal level of any associated wireless interface, in dBm.
+#[cfg(target_os = "linux")]
+pub fn wifi_signal_dbm() -> Option<f32> {
+    let wireless = std::fs::read_to_string("/proc/net/wireless").ok()?;
+    // Two header lines, then `name: status link level noise ...`, with the
+    // quality values followed by a dot when they've been updated.
+    wireless
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (_, fields) = line.split_once(':')?;
+            let level = fields.split_whitespace().nth(2)?;
+            level.trim_end_matches('.').parse::<f32>().ok()
+        })
+        // Interfaces that aren't associated report a level of 0.
+        .filter(|&level| level < 0.0)
+        .reduce(f32::max)
+}
+
+/// Allocated file handles and the system-wide limit, from
+/// `/proc/sys/fs/file-nr`.
+#[cfg(target_os = "linux")]
+pub fn file_handles() -> Option<(u64, u64)> {
+    let file_nr = std::fs::read_to_string("/proc/sys/fs/file-nr").ok()?;
+    let fields: Vec<u64> = file_nr
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    // allocated, allocated but unused (always 0 since Linux 2.6), maximum
+    match fields[..] {
+        [allocated, unused, max] => {
+            Some((allocated.saturating_sub(unused), max))
+        }
+        _ => None,
+    }
+}
+
+/// Open file descriptors of process `pid` and its soft limit on them.
+#[cfg(target_os = "linux")]
+pub fn process_file_descriptors(pid: i32) -> Option<(u64, u64)> {
+    use std::fs::{read_dir, read_to_string};
+
+    let open = read_dir(format!("/proc/{pid}/fd")).ok()?.count() as u64;
+    let limits = read_to_string(format!("/proc/{pid}/limits")).ok()?;
+    let limit = limits
+        .lines()
+        .find_map(|line| line.strip_prefix("Max open files"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    Some((open, limit))
+}
+
+/// States of a TCP socket, as numbered by the kernel.
+#[derive(Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TcpState {
+    Established = 1,
+    SynSent,
+    SynRecv,
+    FinWait1,
+    FinWait2,
+    TimeWait,
+    Close,
+    CloseWait,
+    LastAck,
+    Listen,
+    Closing,
+}
+
+/// Number of IPv4 and IPv6 TCP sockets in any of `states`.
+#[cfg(target_os = "linux")]
+pub fn tcp_connections(states: &[TcpState]) -> Option<usize> {
+    use std::fs::File;
+    use std::io::{BufRead, BufReader};
+
+    let mut count = 0;
+    let mut found = false;
+    for path in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        // A kernel without IPv6 doesn't have tcp6.
+        let Ok(file) = File::open(path) else {
+            continue;
+        };
+        found = true;
+        // After a header line, each line is `sl local remote st ...`, with
+        // the state in hex. Busy servers have lots of these, so they're
+        // streamed rather than read in one go.
+        for line in BufReader::new(file).lines().skip(1) {
+            let line = line.ok()?;
+            let Some(state) = line.split_whitespace().nth(3) else {
+                continue;
+            };
+            let Ok(state) = u8::from_str_radix(state, 16) else {
+                continue;
+            };
+            if states.iter().any(|&wanted| wanted as u8 == state) {
+                count += 1;
+            }
+        }
+    }
+    found.then_some(count)
+}
+
+/// Memory waiting to be written back to disk, and the point at which the
+/// kernel starts making writers wait for it, in bytes.
+#[derive(Clone, Copy)]
+pub struct DirtyMemory {
+    /// `Dirty` plus `Writeback`.
+    pub pending: u64,
+    /// The writeback threshold: `vm.dirty_bytes` if set, otherwise
+    /// `vm.dirty_ratio` percent of available memory.
+    pub limit: u64,
+}
+
+#[cfg(target_os = "linux")]
+pub fn dirty_memory() -> Option<DirtyMemory> {
+    use std::fs::read_to_string;
+
+    let meminfo = read_to_string("/proc/meminfo").ok()?;
+    let kilobytes = |name: &str| {
+        meminfo.lines().find_map(|line| {
+            let value = line.strip_prefix(name)?.strip_prefix(':')?;
+            value.split_whitespace().next()?.parse::<u64>().ok()
+        })
+    };
+    let sysctl = |name: &str| {
+        read_to_string(format!("/proc/sys/vm/{name}"))
+            .ok()?
+            .trim()
+            .parse::<u64>()
+            .ok()
+    };
+    let pending = (kilobytes("Dirty")? + kilobytes("Writeback")?) * 1024;
+    let limit = match sysctl("dirty_bytes") {
+        Some(bytes) if bytes > 0 => bytes,
+        _ => kilobytes("MemAvailable")? * 1024 / 100 * sysctl("dirty_ratio")?,
+    };
+    Some(DirtyMemory { pending, limit })
+}
+
+/// Worst state of any md array: 0 when all are healthy, the progress of a
+/// resync, recovery or reshape while one runs, and 1 if one is degraded or
+/// has a failed member. Routine scrubs, like the monthly `check`, don't
+/// count. Systems without md arrays read as 0.
+#[cfg(target_os = "linux")]
+pub fn mdraid_state() -> Option<f32> {
+    match std::fs::read_to_string("/proc/mdstat") {
+        Ok(mdstat) => Some(parse_mdstat(&mdstat)),
+        // The file only exists once the md driver is loaded.
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            Some(0.0)
+        }
+        Err(_) => None,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn parse_mdstat(mdstat: &str) -> f32 {
+    // Each array starts with an unindented `mdN : active raid1 sda1[0]
+    // sdb1[1](F)` line, followed by indented lines with the member status,
+    // like `[2/1] [U_]`, and an optional progress bar, like
+    // `[==>....]  recovery = 12.6% (...)`.
+    let mut worst = 0f32;
+    let mut arrays = mdstat.split('\n').peekable();
+    while let Some(line) = arrays.next() {
+        if !line.starts_with("md") {
+            continue;
+        }
+        let mut degraded = line.contains("(F)");
+        let mut progress = None;
+        while let Some(detail) =
+            arrays.next_if(|next| next.starts_with(char::is_whitespace))
+        {
+            // A missing member shows up as `_` in the status.
+            let mut statuses = detail.split_whitespace().filter(|field| {
+                field.starts_with('[') && field.ends_with(']')
+            });
+            if statuses.any(|status| status.contains('_')) {
+                degraded = true;
+            }
+            for action in ["resync", "recovery", "reshape"] {
+                let Some((_, rest)) = detail.split_once(action) else {
+                    continue;
+                };
+                // Delayed or pending resyncs have no percentage yet.
+                progress = Some(
+                    rest.trim_start_matches([' ', '='])
+                        .split('%')
+                        .next()
+                        .and_then(|percent| percent.parse::<f32>().ok())
+                        .map_or(0.0, |percent| percent / 100.0),
+                );
+            }
+        }
+        let state = match progress {
+            Some(progress) => progress.clamp(0.0, 1.0),
+            None if degraded => 1.0,
+            None => 0.0,
+        };
+        worst = worst.max(state);
+    }
+    worst
+}
+
+/// Highest maximum frequency of any core, in MHz.
+#[cfg(target_os = "linux")]
+#[allow(clippy::cast_precision_loss)]
+pub fn cpu_max_frequency_mhz() -> Option<f32> {
+    use std::fs::{read_dir, read_to_string};
+
+    read_dir("/sys/devices/system/cpu")
+        .ok()?
+        .filter_map(Result::ok)
+        .filter_map(|cpu| {
+            let max = cpu.path().join("cpufreq/cpuinfo_max_freq");
+            read_to_string(max).ok()?.trim().parse::<u64>().ok()
+        })
+        .max()
+        .filter(|&khz| khz > 0)
+        .map(|khz| khz as f32 / 1000.0)
+}
+
+/// The `some avg10` value from `/proc/pressure/<resource>`, as a fraction.
+///
+/// Only kernels built with `CONFIG_PSI` have these files.
+#[cfg(target_os = "linux")]
+pub fn pressure(resource: &str) -> Option<f32> {
+    let pressure =
+        std::fs::read_to_string(format!("/proc/pressure/{resource}")).ok()?;
+    let some = pressure.lines().find(|line| line.starts_with("some "))?;
+    let avg10 = some
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?;
+    avg10.parse::<f32>().ok().map(|percent| percent / 100.0)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub const fn pressure(_resource: &str) -> Option<f32> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn cpu_times() -> Option<CpuTimes> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn context_switches() -> Option<u64> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn throttle_events() -> Option<u64> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn page_size() -> u64 { 4096 }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn vmstat(_name: &str) -> Option<u64> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn interrupts() -> Option<u64> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn disk_io_bytes() -> Option<u64> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn entropy() -> Option<(u64, u64)> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn arcstats() -> Option<ArcStats> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn network_drops() -> Option<u64> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn wifi_signal_dbm() -> Option<f32> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn file_handles() -> Option<(u64, u64)> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn process_file_descriptors(_pid: i32) -> Option<(u64, u64)> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn tcp_connections(_states: &[TcpState]) -> Option<usize> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn dirty_memory() -> Option<DirtyMemory> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn mdraid_state() -> Option<f32> { None }
+
+#[cfg(not(target_os = "linux"))]
+pub const fn cpu_max_frequency_mhz() -> Option<f32> { None }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} isn't {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn vmstat_counters_are_found_by_name() {
+        let vmstat = "nr_free_pages 1048576
+pgmajfault 4211
+oom_kill 3
+";
+        assert_eq!(parse_vmstat(vmstat, "oom_kill"), Some(3));
+        assert_eq!(parse_vmstat(vmstat, "pgmajfault"), Some(4211));
+    }
+
+    #[test]
+    fn vmstat_counters_missing_on_old_kernels_are_none() {
+        // Kernels before 4.13 have no `oom_kill`.
+        let vmstat = "nr_free_pages 1048576
+pgmajfault 4211
+";
+        assert_eq!(parse_vmstat(vmstat, "oom_kill"), None);
+        assert_eq!(parse_vmstat("", "oom_kill"), None);
+    }
+
+    #[test]
+    fn vmstat_counters_are_found_wherever_they_are() {
+        // Newer kernels add counters before `pgmajfault`.
+        let old = "pgfault 912345
+pgmajfault 4211
+pgrefill 0
+";
+        let new = "nr_free_pages 1048576
+nr_zone_inactive_anon 8192
+pgfault 912345
+pgrefill 0
+pgmajfault 4211
+";
+        assert_eq!(parse_vmstat(old, "pgmajfault"), Some(4211));
+        assert_eq!(parse_vmstat(new, "pgmajfault"), Some(4211));
+    }
+
+    #[test]
+    fn vmstat_counters_need_an_exact_name() {
+        let vmstat = "pgmajfault_file 10
+pgmajfault 4211
+";
+        assert_eq!(parse_vmstat(vmstat, "pgmajfault"), Some(4211));
+        assert_eq!(parse_vmstat(vmstat, "pgmaj"), None);
+    }
+
+    #[test]
+    fn clean_arrays_are_dark() {
+        let mdstat = "Personalities : [raid1]
+md0 : active raid1 sdb1[1] sda1[0]
+      1046528 blocks super 1.2 [2/2] [UU]
+      bitmap: 0/8 pages [0KB], 65536KB chunk
+
+unused devices: <none>
+";
+        assert_near(parse_mdstat(mdstat), 0.0);
+    }
+
+    #[test]
+    fn degraded_arrays_are_fully_on() {
+        let missing = "md0 : active raid1 sda1[0]
+      1046528 blocks super 1.2 [2/1] [U_]
+";
+        assert_near(parse_mdstat(missing), 1.0);
+        let failed = "md0 : active raid1 sdb1[1](F) sda1[0]
+      1046528 blocks super 1.2 [2/1] [U_]
+";
+        assert_near(parse_mdstat(failed), 1.0);
+    }
+
+    #[test]
+    fn recovering_arrays_show_their_progress() {
+        let mdstat = "md0 : active raid1 sdb1[2] sda1[0]
+      1046528 blocks super 1.2 [2/1] [U_]
+      [====>................]  recovery = 23.5% (246784/1046528) \
+                      finish=0.1min speed=123392K/sec
+";
+        assert_near(parse_mdstat(mdstat), 0.235);
+    }
+
+    #[test]
+    fn checks_are_not_counted() {
+        let mdstat = "md0 : active raid1 sdb1[1] sda1[0]
+      1046528 blocks super 1.2 [2/2] [UU]
+      [==>..................]  check = 12.6% (132096/1046528) finish=0.2min \
+                      speed=66048K/sec
+";
+        assert_near(parse_mdstat(mdstat), 0.0);
+    }
+}
@@ -0,0 +1,33 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Energy counters of Intel and AMD CPUs, read from the powercap RAPL
+//! zones in `/sys/class/powercap`.
+use std::fs::read_to_string;
+use std::io;
+use std::path::PathBuf;
+
+fn zone(name: &str) -> PathBuf {
+    PathBuf::from("/sys/class/powercap").join(name)
+}
+
+fn read_u64(name: &str, attribute: &str) -> io::Result<u64> {
+    read_to_string(zone(name).join(attribute))?
+        .trim()
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Energy the zone has used, in microjoules, and the value at which the
+/// counter wraps around.
+///
+/// Since the counters can be used for side-channel attacks, most kernels
+/// only let root read them; that shows up as a permission error.
+pub fn energy_uj(name: &str) -> io::Result<(u64, u64)> {
+    Ok((
+        read_u64(name, "energy_uj")?,
+        read_u64(name, "max_energy_range_uj")?,
+    ))
+}
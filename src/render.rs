@@ -0,0 +1,379 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! What the pad workers show, one PWM period at a time.
+use std::f64::consts::TAU;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::morse::Segment;
+
+/// What a pad shows until the sampler says otherwise.
+#[derive(Clone)]
+pub enum Show {
+    /// A steady duty cycle.
+    Level(f32),
+    /// Blinking fully on and off, `hz` times a second.
+    Blink { hz: f32 },
+    /// Switching between two duty cycles, `hz` times a second.
+    Alternate { low: f32, high: f32, hz: f32 },
+    /// Fading smoothly in and out, `hz` times a second.
+    Breathe { hz: f32 },
+    /// Playing this pattern on a loop.
+    Morse(Arc<[Segment]>),
+    /// Dark, but flashing for `length` once for each of `events`, after
+    /// any flashes still to go.
+    Pulse { events: u32, length: Duration },
+}
+
+impl Show {
+    /// Whether this is what an alert shows.
+    pub const fn is_alert(&self) -> bool {
+        matches!(self, Self::Blink { .. } | Self::Morse(_))
+    }
+}
+
+/// Keeps track of what a pad shows and where its animations are at.
+pub struct Renderer {
+    show: Show,
+    /// How far into a breath the pad is, from 0 to 1. Advanced bit by bit
+    /// rather than read off the clock, so a breath carries on smoothly
+    /// when its rate changes.
+    breath: f64,
+    updated: Instant,
+    /// When the Morse pattern being played started.
+    pattern_started: Instant,
+    /// Flashes still to go in pulse mode.
+    pulses: u32,
+    /// When the current flash started.
+    pulse_started: Option<Instant>,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self {
+            show: Show::Level(0.0),
+            breath: 0.0,
+            updated: Instant::now(),
+            pattern_started: Instant::now(),
+            pulses: 0,
+            pulse_started: None,
+        }
+    }
+
+    pub fn set(&mut self, show: Show) {
+        // A pattern keeps playing while the sampler keeps sending it, and
+        // starts over from the top when it comes back.
+        if matches!(show, Show::Morse(_))
+            && !matches!(self.show, Show::Morse(_))
+        {
+            self.pattern_started = Instant::now();
+        }
+        if let Show::Pulse { events, .. } = show {
+            self.pulses = self.pulses.saturating_add(events);
+        }
+        self.show = show;
+    }
+
+    /// Whether the pad is showing an alert.
+    pub const fn alerting(&self) -> bool { self.show.is_alert() }
+
+    /// Duty cycle for the PWM period starting now.
+    pub fn duty_cycle(&mut self) -> f32 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.updated = now;
+        match &self.show {
+            &Show::Level(duty_cycle) => duty_cycle,
+            &Show::Blink { hz } => square(hz, 0.0, 1.0),
+            &Show::Alternate { low, high, hz } => square(hz, low, high),
+            #[allow(clippy::cast_possible_truncation)]
+            &Show::Breathe { hz } => {
+                self.breath =
+                    f64::from(hz).mul_add(elapsed, self.breath).fract();
+                ((1.0 - (self.breath * TAU).cos()) / 2.0) as f32
+            }
+            Show::Morse(pattern) => {
+                let total: f64 = pattern
+                    .iter()
+                    .map(|segment| segment.length.as_secs_f64())
+                    .sum();
+                let mut into =
+                    now.duration_since(self.pattern_started).as_secs_f64()
+                        % total;
+                for segment in pattern.iter() {
+                    let length = segment.length.as_secs_f64();
+                    if into < length {
+                        return if segment.lit { 1.0 } else { 0.0 };
+                    }
+                    into -= length;
+                }
+                0.0
+            }
+            &Show::Pulse { length, .. } => self.pulse(now, length),
+        }
+    }
+
+    /// Duty cycle in pulse mode: fully on during a flash, and dark
+    /// otherwise. Each flash is followed by a gap as long, so flashes in
+    /// a row stay apart.
+    fn pulse(&mut self, now: Instant, length: Duration) -> f32 {
+        if let Some(started) = self.pulse_started {
+            let into = now.duration_since(started);
+            if into < length {
+                return 1.0;
+            }
+            if into < length * 2 {
+                return 0.0;
+            }
+            self.pulse_started = None;
+        }
+        if self.pulses == 0 {
+            return 0.0;
+        }
+        self.pulses -= 1;
+        self.pulse_started = Some(now);
+        1.0
+    }
+}
+
+/// PWM cycles up to this long count as brightness rather than flashing.
+const FUSION_PERIOD: Duration = Duration::from_millis(25);
+
+/// Smallest change in the duty cycle from one PWM cycle to the next that
+/// counts as the pad flashing, rather than fading.
+const FLASH_STEP: f32 = 0.2;
+
+/// Keeps a pad from flashing faster than `max_blink_hz`, whatever it's
+/// told to show, by holding each flash until it's been on, or off, for
+/// long enough.
+///
+/// Every duty cycle goes through here last, just before PWM, so no display
+/// mode gets round it.
+pub struct Limiter {
+    /// Shortest time the pad may stay on, or off, for.
+    gap: Duration,
+    period: Duration,
+    shown: f32,
+    /// When the pad last flashed on or off.
+    flashed: Option<Instant>,
+}
+
+impl Limiter {
+    pub fn new(max_blink_hz: f32, period: Duration) -> Self {
+        Self {
+            gap: Duration::from_secs_f32(0.5 / max_blink_hz),
+            period,
+            shown: 0.0,
+            flashed: None,
+        }
+    }
+
+    /// Duty cycle for the PWM cycle starting at `start`, when it should be
+    /// `duty_cycle`.
+    pub fn limit(&mut self, duty_cycle: f32, start: Instant) -> f32 {
+        let duty_cycle = self.without_flicker(duty_cycle);
+        if (duty_cycle - self.shown).abs() >= FLASH_STEP {
+            let too_soon = self.flashed.is_some_and(|flashed| {
+                start.duration_since(flashed) < self.gap
+            });
+            if too_soon {
+                return self.shown;
+            }
+            self.flashed = Some(start);
+        }
+        self.shown = duty_cycle;
+        duty_cycle
+    }
+
+    /// `duty_cycle`, or fully on or off, whichever is nearer, if PWM is
+    /// slow enough to see and would switch the pad on and off again too
+    /// quickly within each cycle.
+    fn without_flicker(&self, duty_cycle: f32) -> f32 {
+        if self.period <= FUSION_PERIOD
+            || duty_cycle <= 0.0
+            || duty_cycle >= 1.0
+        {
+            return duty_cycle;
+        }
+        let on = self.period.mul_f32(duty_cycle);
+        if on < self.gap || self.period.saturating_sub(on) < self.gap {
+            duty_cycle.round()
+        } else {
+            duty_cycle
+        }
+    }
+}
+
+/// How far into a cycle of `hz` the wall clock is, from 0 to 1.
+///
+/// Going by the wall clock rather than counting periods keeps animations
+/// steady however late the worker wakes up, and in step across pads.
+pub fn phase(hz: f32) -> f64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    (now * f64::from(hz)).fract()
+}
+
+/// `high` for the first half of each cycle of `hz` by the wall clock, and
+/// `low` for the second.
+fn square(hz: f32, low: f32, high: f32) -> f32 {
+    if phase(hz) < 0.5 {
+        high
+    } else {
+        low
+    }
+}
+
+/// Rounds `duty_cycle` to the nearest of `levels` evenly spaced from 0 to
+/// 1, so small wobbles in the value don't change the PWM timing at all.
+#[allow(clippy::cast_precision_loss)]
+pub fn quantize(duty_cycle: f32, levels: u32) -> f32 {
+    let steps = levels.saturating_sub(1).max(1) as f32;
+    ((duty_cycle.clamp(0.0, 1.0) * steps).round() / steps).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dither::Dither;
+
+    /// Duty cycles from a little below 0 to a little above 1.
+    fn duty_cycles() -> impl Iterator<Item = f32> {
+        (-10..=110i8).map(|step| f32::from(step) / 100.0)
+    }
+
+    #[test]
+    fn quantized_duty_cycles_stay_in_range() {
+        for levels in [0, 1, 2, 3, 16, 1000, u32::MAX] {
+            for duty_cycle in duty_cycles() {
+                let quantized = quantize(duty_cycle, levels);
+                assert!(
+                    (0.0..=1.0).contains(&quantized),
+                    "{} with {} levels is {}",
+                    duty_cycle,
+                    levels,
+                    quantized
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quantizing_rounds_to_the_nearest_level() {
+        assert!((quantize(0.3, 3) - 0.5).abs() < f32::EPSILON);
+        assert!(quantize(0.2, 3).abs() < f32::EPSILON);
+        assert!((quantize(0.8, 3) - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn quantizing_keeps_the_ends() {
+        for levels in [2, 16, 1000] {
+            assert!(quantize(0.0, levels).abs() < f32::EPSILON);
+            assert!((quantize(1.0, levels) - 1.0).abs() < f32::EPSILON);
+        }
+    }
+
+    /// Runs `pattern`, one duty cycle per cycle of `period`, through a
+    /// limiter, and returns when the pad flashed on or off.
+    fn flashes(
+        max_blink_hz: f32,
+        period: Duration,
+        pattern: impl IntoIterator<Item = f32>,
+    ) -> Vec<Instant> {
+        let mut limiter = Limiter::new(max_blink_hz, period);
+        let mut start = Instant::now();
+        let mut shown = 0.0;
+        let mut flashes = vec![];
+        for duty_cycle in pattern {
+            let held = limiter.limit(duty_cycle, start);
+            if (held - shown).abs() >= FLASH_STEP {
+                flashes.push(start);
+            }
+            shown = held;
+            start += period;
+        }
+        flashes
+    }
+
+    /// Checks that `flashes` over `seconds` stay within `max_blink_hz`:
+    /// never closer together than half a blink, and no more than two a
+    /// blink overall, give or take the first.
+    fn assert_within(flashes: &[Instant], max_blink_hz: f32, seconds: f32) {
+        let gap = Duration::from_secs_f32(0.5 / max_blink_hz);
+        for pair in flashes.windows(2) {
+            assert!(pair[1].duration_since(pair[0]) >= gap);
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let rate = flashes.len() as f32 / seconds;
+        let limit = 2.0f32.mul_add(max_blink_hz, 1.0 / seconds);
+        assert!(rate <= limit, "{} flashes/s", rate);
+    }
+
+    #[test]
+    fn toggling_every_cycle_is_held_to_the_limit() {
+        // 10 s of 20 ms cycles, switching every time.
+        let pattern =
+            (0..500).map(|cycle| f32::from(u8::from(cycle % 2 == 0)));
+        let flashes = flashes(3.0, Duration::from_millis(20), pattern);
+        assert_within(&flashes, 3.0, 10.0);
+        // Held down, but not stopped.
+        assert!(flashes.len() >= 50);
+    }
+
+    #[test]
+    fn random_flashing_is_held_to_the_limit() {
+        let mut dither = Dither::new(1.0, Some(3));
+        let pattern =
+            (0..1000).map(
+                |_| {
+                    if dither.next_offset() > 0.0 {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                },
+            );
+        let flashes = flashes(5.0, Duration::from_millis(10), pattern);
+        assert_within(&flashes, 5.0, 10.0);
+    }
+
+    #[test]
+    fn slow_blinking_goes_through_untouched() {
+        // 1 Hz on 20 ms cycles: 25 on, 25 off.
+        let pattern: Vec<f32> = (0..500)
+            .map(|cycle| f32::from(u8::from(cycle % 50 < 25)))
+            .collect();
+        let mut limiter = Limiter::new(3.0, Duration::from_millis(20));
+        let mut start = Instant::now();
+        for &duty_cycle in &pattern {
+            let held = limiter.limit(duty_cycle, start);
+            assert!((held - duty_cycle).abs() < f32::EPSILON);
+            start += Duration::from_millis(20);
+        }
+    }
+
+    #[test]
+    fn fading_isnt_flashing() {
+        // Steps smaller than a flash are never held back.
+        let pattern = (0..=100u8).map(|step| f32::from(step) / 100.0);
+        let flashes = flashes(1.0, Duration::from_millis(20), pattern);
+        assert!(flashes.is_empty());
+    }
+
+    #[test]
+    fn slow_pwm_doesnt_flicker_within_a_cycle() {
+        // At 500 ms a cycle, 50 ms on is a visible flash.
+        let limiter = Limiter::new(3.0, Duration::from_millis(500));
+        assert!(limiter.without_flicker(0.1).abs() < f32::EPSILON);
+        assert!((limiter.without_flicker(0.95) - 1.0).abs() < f32::EPSILON);
+        assert!((limiter.without_flicker(0.5) - 0.5).abs() < f32::EPSILON);
+        // Fast PWM fuses into brightness whatever the duty cycle.
+        let fast = Limiter::new(3.0, Duration::from_millis(20));
+        assert!((fast.without_flicker(0.1) - 0.1).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,96 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Several metrics taking turns on the same pads.
+use std::time::{Duration, Instant};
+
+use sysinfo::System;
+
+use crate::metric::Metric;
+use crate::render::Show;
+use crate::style::Style;
+
+/// How long the pads go dark between one metric and the next, so it's
+/// clear where one ends.
+const SWITCH_GAP: Duration = Duration::from_millis(250);
+
+/// The metrics a group switches to after its own, and which one is up.
+pub struct Rotation {
+    /// Each with display settings of its own, so smoothing and alerts
+    /// don't mix between metrics.
+    others: Vec<(Metric, Style)>,
+    dwell: Duration,
+    /// The metric showing: 0 for the group's own, then the others in
+    /// order.
+    current: usize,
+    since: Instant,
+    /// Whether the pads are dark for the switch to `current`.
+    switching: bool,
+}
+
+impl Rotation {
+    /// Shows each metric for `dwell` before going on to the next.
+    pub fn new(others: Vec<(Metric, Style)>, dwell: Duration) -> Self {
+        Self {
+            others,
+            dwell,
+            current: 0,
+            since: Instant::now(),
+            switching: false,
+        }
+    }
+
+    pub fn metrics(&self) -> impl Iterator<Item = &Metric> {
+        self.others.iter().map(|(metric, _)| metric)
+    }
+
+    pub fn styles(&self) -> impl Iterator<Item = &Style> {
+        self.others.iter().map(|(_, style)| style)
+    }
+
+    pub fn styles_mut(&mut self) -> impl Iterator<Item = &mut Style> {
+        self.others.iter_mut().map(|(_, style)| style)
+    }
+
+    /// Display settings of the metric showing, if it isn't the group's
+    /// own.
+    pub fn current(&self) -> Option<&Style> {
+        self.styles().nth(self.current.checked_sub(1)?)
+    }
+
+    /// Samples every other metric, so none of them miss an alert, and
+    /// returns what the pads show: `own`, what the group's own metric
+    /// would, or one of the others.
+    ///
+    /// A metric with an alert on stays up until it's over, and one that
+    /// raises an alert while another is up takes over straight away.
+    pub fn pick(&mut self, own: Vec<Show>, system: &System) -> Vec<Show> {
+        let segments = own.len();
+        let mut shows = vec![own];
+        for (metric, style) in &mut self.others {
+            let value = metric.sample(system);
+            shows.push(style.apply(value, metric.events(), segments));
+        }
+        let alerting = |shows: &[Show]| shows.iter().any(Show::is_alert);
+        let now = Instant::now();
+        if alerting(&shows[self.current]) {
+            self.since = now;
+            self.switching = false;
+        } else if let Some(index) = shows.iter().position(|s| alerting(s)) {
+            self.current = index;
+            self.since = now;
+            self.switching = false;
+        } else if now.duration_since(self.since) >= self.dwell {
+            self.current = (self.current + 1) % shows.len();
+            self.since = now;
+            self.switching = true;
+        }
+        if self.switching && now.duration_since(self.since) < SWITCH_GAP {
+            return vec![Show::Level(0.0); segments];
+        }
+        self.switching = false;
+        shows.swap_remove(self.current)
+    }
+}
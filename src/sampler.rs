@@ -0,0 +1,171 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! The thread that samples every metric and tells the pads what to show.
+use std::sync::mpsc::Sender;
+use std::thread::sleep;
+use std::time::Duration;
+
+use midir::MidiOutputConnection;
+use sysinfo::{RefreshKind, System, SystemExt};
+
+use crate::color::Rgb;
+use crate::config::{Device, Group};
+use crate::dimming::{Idle, Night};
+use crate::paging::{Paging, Turn};
+use crate::render::Show;
+use crate::utility::{mk2_pad_colors, PADS};
+use crate::{connect, shutdown, watchdog};
+
+const REFRESH_INTERVAL: u64 = 100;
+
+pub struct Sampler {
+    /// Each page's groups, with the channels to their pads' workers.
+    pages: Vec<Vec<(Vec<Sender<Show>>, Group)>>,
+    /// The channel to every pad's worker.
+    workers: Vec<Sender<Show>>,
+    paging: Paging,
+    colors: Option<Colors>,
+    idle: Option<Idle>,
+    night: Vec<Night>,
+}
+
+impl Sampler {
+    pub fn new(
+        pages: Vec<Vec<(Vec<Sender<Show>>, Group)>>,
+        workers: Vec<Sender<Show>>,
+        paging: Paging,
+        device: Device,
+        idle: Option<Idle>,
+        night: Vec<Night>,
+    ) -> Self {
+        Self {
+            pages,
+            workers,
+            paging,
+            colors: Colors::connect(device),
+            idle,
+            night,
+        }
+    }
+
+    /// Samples away, every `REFRESH_INTERVAL` ms. Left running when
+    /// shutting down: nothing is lost once the workers stop listening.
+    ///
+    /// Every page is sampled, so none of them miss an alert or have to
+    /// catch up once it's their turn, but only the one up is shown.
+    pub fn run(mut self) {
+        let refresh_kind = self
+            .pages
+            .iter()
+            .flatten()
+            .fold(RefreshKind::new(), |kind, (_, group)| {
+                group.refresh_kind(kind)
+            });
+        // The component, network and disk lists only need to be built
+        // once; afterwards it's enough to refresh the values.
+        let mut system = System::new_with_specifics(
+            refresh_kind
+                .with_components_list()
+                .with_networks_list()
+                .with_disks_list(),
+        );
+        loop {
+            system.refresh_specifics(refresh_kind);
+            let turn = self.paging.update();
+            for (page, groups) in self.pages.iter_mut().enumerate() {
+                let up =
+                    matches!(turn, Turn::Page(current) if current == page);
+                for (txs, group) in groups {
+                    let shows = group.sample(&system);
+                    if !up {
+                        continue;
+                    }
+                    for (tx, show) in txs.iter().zip(shows) {
+                        // Only fails once the worker has stopped for good.
+                        let _ = tx.send(show);
+                    }
+                }
+            }
+            Night::update(&self.night);
+            match turn {
+                Turn::Blink => {
+                    for tx in &self.workers {
+                        let _ = tx.send(Show::Level(1.0));
+                    }
+                }
+                Turn::Page(current) => {
+                    let groups = &self.pages[current];
+                    if let Some(idle) = &mut self.idle {
+                        idle.update(
+                            groups.iter().map(|(_, group)| {
+                                group.current_style().shown()
+                            }),
+                        );
+                    }
+                    if let Some(colors) = &mut self.colors {
+                        colors.update(groups.iter().map(|(_, group)| group));
+                    }
+                }
+            }
+            watchdog::sampled();
+            sleep(Duration::from_millis(REFRESH_INTERVAL));
+        }
+    }
+}
+
+/// Keeps the colors of an LPD8 MK2's pads in line with their values.
+struct Colors {
+    connection: MidiOutputConnection,
+    /// The colors last sent, so they're only sent again once they change.
+    sent: Option<[Rgb; 8]>,
+}
+
+impl Colors {
+    /// `None` for the original LPD8, which has no colors to set.
+    fn connect(device: Device) -> Option<Self> {
+        if device != Device::Lpd8Mk2 {
+            return None;
+        }
+        connect("Colors")
+            .map_err(|err| {
+                eprintln!("Couldn't connect to color the pads: {err}");
+            })
+            .ok()
+            .map(|connection| Self {
+                connection,
+                sent: None,
+            })
+    }
+
+    /// Colors every pad of `groups`. A bar or history takes the color of
+    /// its current value, and pads without a sampled metric, like the
+    /// clock, are white.
+    fn update<'a>(&mut self, groups: impl Iterator<Item = &'a Group>) {
+        let white = Rgb {
+            red: 255,
+            green: 255,
+            blue: 255,
+        };
+        let mut frame = [white; 8];
+        for group in groups {
+            let color = group.current_style().color();
+            for pad in &group.pads {
+                if let Some(index) = PADS.iter().position(|other| other == pad)
+                {
+                    frame[index] = color;
+                }
+            }
+        }
+        if self.sent == Some(frame) {
+            return;
+        }
+        if let Err(err) = self.connection.send(&mk2_pad_colors(&frame)) {
+            eprintln!("Couldn't color the pads: {err}");
+            shutdown::request();
+        }
+        self.sent = Some(frame);
+    }
+}
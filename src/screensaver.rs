@@ -0,0 +1,45 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! A light sweeping back and forth across the pads while the machine is
+//! idle, which takes over every pad from what it'd show otherwise.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::render;
+use crate::utility::{Pad, PADS};
+
+/// Whether the screensaver is showing.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Seconds the light takes to go across the pads and back.
+const SWEEP: f32 = 3.0;
+
+/// How many pads either side of the light are lit at all, fading out
+/// towards the edges, so it leaves a short trail.
+const WIDTH: f32 = 1.5;
+
+/// Starts the screensaver, from the next PWM cycle on.
+pub fn start() { ACTIVE.store(true, Ordering::Relaxed); }
+
+/// Hands every pad straight back to its own worker.
+pub fn stop() { ACTIVE.store(false, Ordering::Relaxed); }
+
+/// Duty cycle for `pad` while the screensaver is showing, or `None` if it
+/// isn't and the pad should show its own.
+///
+/// Follows the wall clock, like blinking, so every pad agrees on where the
+/// light is without having to talk to the others.
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+pub fn duty_cycle(pad: Pad) -> Option<f32> {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return None;
+    }
+    let index = PADS.iter().position(|&other| other == pad)? as f32;
+    let last = (PADS.len() - 1) as f32;
+    // Goes from 0 to 1 and back once a sweep.
+    let phase = render::phase(1.0 / SWEEP) as f32;
+    let light = (1.0 - (phase * 2.0 - 1.0).abs()) * last;
+    Some((1.0 - (index - light).abs() / WIDTH).clamp(0.0, 1.0))
+}
@@ -0,0 +1,38 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Stopping the whole program in one go, whether it's asked to by a signal
+//! or because a pad worker lost its connection, so that every pad can be
+//! switched off on the way out.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_signal(_signal: libc::c_int) {
+    REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Catches `SIGINT` and `SIGTERM` instead of dying on them; see
+/// [`requested`].
+#[cfg(unix)]
+pub fn listen() {
+    // SAFETY: the handler only sets an atomic flag, which is
+    // async-signal-safe.
+    unsafe {
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            libc::signal(signal, on_signal as *const () as libc::sighandler_t);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub const fn listen() {}
+
+/// Asks every thread that checks [`requested`] to wrap up.
+pub fn request() { REQUESTED.store(true, Ordering::Relaxed); }
+
+/// Whether a signal or [`request`] asked the process to stop.
+pub fn requested() -> bool { REQUESTED.load(Ordering::Relaxed) }
@@ -0,0 +1,62 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Drive health, as reported by `smartctl`.
+use std::process::Command;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::command;
+
+/// ATA attributes counting sectors the drive gave up on: reallocated,
+/// pending reallocation, and offline uncorrectable.
+const BAD_SECTOR_ATTRIBUTES: [u64; 3] = [5, 197, 198];
+
+/// How long `smartctl` may take to read a drive. Waking a spun-down disk
+/// takes a few seconds.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sum of the bad sector counts of `device`, or why they couldn't be read.
+///
+/// NVM Express drives don't have those attributes, so their media error
+/// count is used instead.
+pub fn bad_sectors(device: &str) -> Result<u64, String> {
+    // smartctl's exit status is a bit mask that's also set for merely
+    // worrying drives, so it's the JSON that decides.
+    let (_, output) = command::output_with_status(
+        Command::new("smartctl").args(["--attributes", "--json", device]),
+        TIMEOUT,
+    )
+    .ok_or_else(|| format!("Couldn't run smartctl on {device}"))?;
+    let report: Value = serde_json::from_str(&output)
+        .map_err(|err| format!("smartctl printed invalid JSON: {err}"))?;
+    if let Some(table) = report["ata_smart_attributes"]["table"].as_array() {
+        return Ok(table
+            .iter()
+            .filter(|attribute| {
+                attribute["id"]
+                    .as_u64()
+                    .is_some_and(|id| BAD_SECTOR_ATTRIBUTES.contains(&id))
+            })
+            .filter_map(|attribute| attribute["raw"]["value"].as_u64())
+            .sum());
+    }
+    if let Some(errors) =
+        report["nvme_smart_health_information_log"]["media_errors"].as_u64()
+    {
+        return Ok(errors);
+    }
+    let messages: Vec<&str> = report["smartctl"]["messages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|message| message["string"].as_str())
+        .collect();
+    Err(format!(
+        "{device} doesn't report SMART attributes: {}",
+        messages.join("; ")
+    ))
+}
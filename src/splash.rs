@@ -0,0 +1,50 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! A short animation at startup, showing the connection works and which
+//! pad is which.
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use midir::MidiOutputConnection;
+
+use crate::shutdown;
+use crate::utility::{note_off, note_on, Pad, PADS};
+
+const STEP: Duration = Duration::from_millis(150);
+const ALL_ON: Duration = Duration::from_millis(600);
+
+/// Lights the pads one at a time in order, then all of them together.
+/// Returns false if a signal cut it short; every pad is dark again either
+/// way.
+pub fn play(connection: &mut MidiOutputConnection) -> bool {
+    for pad in PADS {
+        if !flash(connection, &[pad], STEP) {
+            return false;
+        }
+    }
+    flash(connection, &PADS, ALL_ON)
+}
+
+/// Lights `pads` for `length`, or until a signal arrives.
+fn flash(
+    connection: &mut MidiOutputConnection,
+    pads: &[Pad],
+    length: Duration,
+) -> bool {
+    // Just for show: the pad workers report a connection that stopped
+    // working, and pads that did light up still go dark below.
+    for &pad in pads {
+        let _ = connection.send(&note_on(pad));
+    }
+    let end = Instant::now() + length;
+    while !shutdown::requested() && Instant::now() < end {
+        sleep(Duration::from_millis(10));
+    }
+    for &pad in pads {
+        let _ = connection.send(&note_off(pad));
+    }
+    !shutdown::requested()
+}
@@ -0,0 +1,1244 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! How a pad turns its metric's value into brightness.
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::color::{self, Rgb, DEFAULT_GRADIENT};
+use crate::dither::Dither;
+use crate::morse::{self, Segment};
+use crate::render::Show;
+use crate::utility::{Pad, PADS};
+
+/// LEDs look far brighter at low duty cycles than the duty cycle suggests,
+/// so by default values are squared before they're shown.
+const DEFAULT_GAMMA: f32 = 2.0;
+
+/// Length of one on/off cycle, in milliseconds.
+const DEFAULT_PERIOD: f32 = 20.0;
+
+/// Weight of each new sample in the moving average. With the sampler's
+/// 100 ms refresh, a jump takes about a second to fully show.
+const DEFAULT_SMOOTHING: f32 = 0.3;
+
+/// How far a value has to move back past a boundary before the pad
+/// switches back.
+const DEFAULT_HYSTERESIS: f32 = 0.03;
+
+/// Levels below this are shown as dark, rather than as a flash too short
+/// to see that still costs two MIDI messages every cycle.
+const DEFAULT_DEADBAND: f32 = 0.02;
+
+const fn default_change_window() -> f32 { 5.0 }
+
+/// Showing how fast the value changes rather than the value itself.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Change {
+    /// Change per second shown at full brightness.
+    full: f32,
+    /// Seconds the change is measured over, so noise doesn't drown it
+    /// out.
+    #[serde(default = "default_change_window")]
+    window: f32,
+    /// Only shows the value going up, not down.
+    #[serde(default)]
+    rising: bool,
+    #[serde(skip)]
+    samples: VecDeque<(f32, Instant)>,
+}
+
+impl Change {
+    fn validate(&self) -> Result<(), String> {
+        if !(self.full > 0.0 && self.full.is_finite()) {
+            Err("change.full must be positive".into())
+        } else if !(self.window > 0.0 && self.window.is_finite()) {
+            Err("change.window must be positive".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// How fast the value has moved over the window, from 0 to 1. The
+    /// first sample has nothing to compare to, so it's 0.
+    fn update(&mut self, value: f32) -> f32 {
+        let now = Instant::now();
+        self.samples.push_back((value, now));
+        // Keeps one sample from before the window, so the change is
+        // measured over the whole of it.
+        while self.samples.len() > 2
+            && now.duration_since(self.samples[1].1).as_secs_f32()
+                >= self.window
+        {
+            self.samples.pop_front();
+        }
+        let (oldest, then) = self.samples[0];
+        let elapsed = now.duration_since(then).as_secs_f32();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        let rate = (value - oldest) / elapsed;
+        let rate = if self.rising {
+            rate.max(0.0)
+        } else {
+            rate.abs()
+        };
+        (rate / self.full).clamp(0.0, 1.0)
+    }
+}
+
+/// Which side of a boundary a value is on, only changing sides once the
+/// value has moved clearly past it.
+#[derive(Clone, Copy, Default)]
+struct Schmitt {
+    on: bool,
+}
+
+impl Schmitt {
+    /// Switches on once `value` reaches `threshold`, and off once it's
+    /// dropped to `threshold - delta`.
+    fn update(&mut self, value: f32, threshold: f32, delta: f32) -> bool {
+        self.on = if self.on {
+            value > threshold - delta
+        } else {
+            value >= threshold
+        };
+        self.on
+    }
+}
+
+/// Maps a value from 0 to 1 onto the duty cycle it's shown with.
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Curve {
+    /// The value raised to this power.
+    Gamma(f32),
+    /// Straight lines between these duty cycles, spread evenly from a
+    /// value of 0 to a value of 1.
+    Table(Vec<f32>),
+}
+
+impl Curve {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Gamma(gamma) if gamma.is_nan() || *gamma <= 0.0 => {
+                Err("curve must be positive".into())
+            }
+            Self::Table(points) if points.len() < 2 => {
+                Err("curve needs at least two points".into())
+            }
+            Self::Table(points)
+                if points.iter().any(|point| !(0.0..=1.0).contains(point)) =>
+            {
+                Err("curve points must be between 0 and 1".into())
+            }
+            Self::Table(points)
+                if points.windows(2).any(|pair| pair[1] < pair[0]) =>
+            {
+                Err("curve points can't decrease".into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn apply(&self, value: f32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+        match self {
+            Self::Gamma(gamma) => value.powf(*gamma),
+            Self::Table(points) => {
+                let position = value * (points.len() - 1) as f32;
+                let index = (position as usize).min(points.len() - 2);
+                let (from, to) = (points[index], points[index + 1]);
+                (to - from).mul_add(position - index as f32, from)
+            }
+        }
+    }
+}
+
+/// Lowest value a logarithmic scale shows, three decades below the top.
+const DEFAULT_LOG_FLOOR: f32 = 0.001;
+
+/// How values are spread over the pad's brightness.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scaling {
+    #[default]
+    Linear,
+    /// Each decade of the value gets the same share, from `log_floor` and
+    /// below at 0 up to 1.
+    Log,
+}
+
+impl Scaling {
+    fn apply(self, value: f32, log_floor: f32) -> f32 {
+        match self {
+            Self::Linear => value,
+            Self::Log if value <= log_floor => 0.0,
+            Self::Log => ((value / log_floor).log10() / -log_floor.log10())
+                .clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// Length of a flash in pulse mode, in milliseconds.
+const DEFAULT_PULSE: f32 = 300.0;
+
+/// Brightnesses alternate mode switches between, and how often.
+const DEFAULT_ALTERNATE: [f32; 2] = [0.2, 0.6];
+const DEFAULT_ALTERNATE_HZ: f32 = 1.0;
+
+/// Duty cycles a pad is rounded to with `quantize = true`.
+const DEFAULT_LEVELS: u32 = 16;
+
+/// Rounding the duty cycle to a few levels: `true` for the default number
+/// of them, or how many.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(untagged)]
+pub enum Quantize {
+    On(bool),
+    Levels(u32),
+}
+
+/// Seconds per breath for a value of 0 and of 1.
+const SLOWEST_BREATH: f32 = 6.0;
+const FASTEST_BREATH: f32 = 0.5;
+
+/// How a pad shows its value.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// As brightness.
+    #[default]
+    Steady,
+    /// As how fast the pad breathes in and out, from slow breaths when
+    /// idle to quick pulses at the top.
+    Breathe,
+    /// Dark, with a short flash for each event the metric counts.
+    Pulse,
+    /// For metrics that are either on or off: switching back and forth
+    /// between two brightnesses while on, and dark while off.
+    Alternate,
+}
+
+const fn default_alert_hz() -> f32 { 2.0 }
+
+const fn default_morse_dot() -> f32 { 0.15 }
+
+/// Blinking the pad, or spelling out `morse`, while its metric is too
+/// high.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Alert {
+    /// Value, before any display settings, from which the alert is on.
+    above: f32,
+    /// Blinks per second.
+    #[serde(default = "default_alert_hz")]
+    hz: f32,
+    /// Seconds the alert keeps going after the value has dropped back.
+    #[serde(default)]
+    hold: f32,
+    /// Text to blink in Morse code instead, over and over.
+    #[serde(default)]
+    morse: Option<String>,
+    /// Length of a Morse dot, in seconds.
+    #[serde(default = "default_morse_dot")]
+    dot: f32,
+    /// Keeps the alert going, even once the value is back to normal,
+    /// until it's acknowledged.
+    #[serde(default)]
+    latch: bool,
+    #[serde(skip)]
+    latched: bool,
+    #[serde(skip)]
+    pattern: Option<Arc<[Segment]>>,
+    #[serde(skip)]
+    raised: Schmitt,
+    #[serde(skip)]
+    last_raised: Option<Instant>,
+}
+
+impl Alert {
+    fn validate(&self) -> Result<(), String> {
+        if !(self.above > 0.0 && self.above <= 1.0) {
+            Err("alert.above must be above 0 and at most 1".into())
+        } else if !(self.hz > 0.0 && self.hz <= 50.0) {
+            Err("alert.hz must be above 0 and at most 50".into())
+        } else if !(self.hold >= 0.0 && self.hold.is_finite()) {
+            Err("alert.hold can't be negative".into())
+        } else if !(self.dot > 0.0 && self.dot <= 2.0) {
+            Err("alert.dot must be above 0 and at most 2 seconds".into())
+        } else {
+            self.morse.as_ref().map_or(Ok(()), |text| {
+                morse::compile(text, Duration::from_secs_f32(self.dot))
+                    .map(drop)
+                    .map_err(|err| format!("alert.morse: {err}"))
+            })
+        }
+    }
+
+    /// What the pad shows while the alert is on.
+    fn show(&mut self) -> Show {
+        let Some(text) = &self.morse else {
+            return Show::Blink { hz: self.hz };
+        };
+        let dot = Duration::from_secs_f32(self.dot);
+        let pattern = self.pattern.get_or_insert_with(|| {
+            // Checked by validate.
+            morse::compile(text, dot).unwrap_or_default().into()
+        });
+        Show::Morse(Arc::clone(pattern))
+    }
+
+    /// Whether the alert is on, now that the metric reads `value`.
+    fn update(&mut self, value: f32, delta: f32) -> bool {
+        let now = Instant::now();
+        let was_raised = self.raised.on;
+        if self.raised.update(value, self.above, delta) {
+            self.last_raised = Some(now);
+            // Only latches as the value goes up again, so acknowledging
+            // sticks while it stays up.
+            self.latched |= self.latch && !was_raised;
+        }
+        self.latched
+            || self.last_raised.is_some_and(|raised| {
+                now.duration_since(raised).as_secs_f32() <= self.hold
+            })
+    }
+}
+
+/// Display settings, either for a single pad or, in the `[display]` table,
+/// for all of them. Anything a pad doesn't set comes from `[display]`.
+#[derive(Clone, Default, Deserialize)]
+pub struct Style {
+    /// Defaults to a gamma of 2.
+    #[serde(default)]
+    curve: Option<Curve>,
+    /// Weight of each new sample in an exponential moving average; 1 turns
+    /// smoothing off.
+    #[serde(default)]
+    smoothing: Option<f32>,
+    #[serde(skip)]
+    smoothed: Option<f32>,
+    /// How far a value has to drop back before a pad that lit up goes
+    /// dark again, or one that went fully on starts dimming.
+    #[serde(default)]
+    hysteresis: Option<f32>,
+    #[serde(skip)]
+    lit: Schmitt,
+    #[serde(skip)]
+    full: Schmitt,
+    /// Length of one on/off cycle, in milliseconds.
+    #[serde(default)]
+    period: Option<f32>,
+    #[serde(default)]
+    quantize: Option<Quantize>,
+    /// How much the length of each cycle may change at random, as a share
+    /// of the period, so it doesn't beat against the LED driver or a
+    /// camera. Off unless set.
+    #[serde(default)]
+    dither: Option<f32>,
+    /// Seed for the dithering, to get the same jitter every run.
+    #[serde(default)]
+    dither_seed: Option<u64>,
+    /// How far into its period the pad's cycle starts, from 0 to 1.
+    /// Defaults to spreading the pads evenly, so they don't all switch on
+    /// at once.
+    #[serde(default)]
+    phase: Option<f32>,
+    /// Turns the pad into an indicator: fully on from this value up, and
+    /// off below it, give or take the hysteresis.
+    #[serde(default)]
+    threshold: Option<f32>,
+    #[serde(skip)]
+    above: Schmitt,
+    #[serde(default)]
+    alert: Option<Alert>,
+    #[serde(default)]
+    change: Option<Change>,
+    #[serde(default)]
+    mode: Option<Mode>,
+    /// Length of a flash in pulse mode, in milliseconds.
+    #[serde(default)]
+    pulse: Option<f32>,
+    #[serde(skip)]
+    events_seen: Option<u64>,
+    /// The two brightnesses alternate mode switches between.
+    #[serde(default)]
+    alternate: Option<[f32; 2]>,
+    /// Switches per second, there and back, in alternate mode.
+    #[serde(default)]
+    alternate_hz: Option<f32>,
+    /// Seconds a peak takes to fade back down to nothing, so short spikes
+    /// stay visible for a while. Off unless set.
+    #[serde(default)]
+    peak_hold: Option<f32>,
+    #[serde(skip)]
+    peak: Option<(f32, Instant)>,
+    /// How fast the pad may dim, as a share of full brightness per
+    /// second, like a VU meter: rises still show at once. Off unless set.
+    #[serde(default)]
+    release: Option<f32>,
+    #[serde(skip)]
+    released: Option<(f32, Instant)>,
+    /// Lowest duty cycle the pad shows while the value isn't 0, since
+    /// very short pulses are next to invisible.
+    #[serde(default)]
+    floor: Option<f32>,
+    /// Level below which the pad is dark, before `floor` comes into it.
+    /// Alerts and thresholds still go by the value itself.
+    #[serde(default)]
+    deadband: Option<f32>,
+    /// Shows 1 minus the value, for metrics like battery charge where low
+    /// is what needs attention. Thresholds and alerts still go by the
+    /// value itself.
+    #[serde(default)]
+    invert: Option<bool>,
+    /// Values the pad goes from dark to full over, like `[0.45, 0.95]`;
+    /// anything outside is clamped.
+    #[serde(default)]
+    range: Option<[f32; 2]>,
+    #[serde(default)]
+    scaling: Option<Scaling>,
+    #[serde(default)]
+    log_floor: Option<f32>,
+    /// Gradient the pad's color follows on an LPD8 MK2.
+    #[serde(default)]
+    colors: Option<Vec<Rgb>>,
+    /// The last value shown, from 0 to 1, for picking the color and
+    /// telling whether the machine is idle.
+    #[serde(skip)]
+    shown: f32,
+}
+
+impl Style {
+    // One arm per setting.
+    #[allow(clippy::too_many_lines)]
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self {
+                smoothing: Some(smoothing),
+                ..
+            } if !(*smoothing > 0.0 && *smoothing <= 1.0) => {
+                Err("smoothing must be above 0 and at most 1".into())
+            }
+            Self {
+                hysteresis: Some(hysteresis),
+                ..
+            } if !(0.0..0.5).contains(hysteresis) => {
+                Err("hysteresis must be at least 0 and below 0.5".into())
+            }
+            Self {
+                period: Some(period),
+                ..
+            } if !(1.0..=10_000.0).contains(period) => {
+                Err("period must be from 1 to 10000 ms".into())
+            }
+            Self {
+                peak_hold: Some(peak_hold),
+                ..
+            } if !(*peak_hold > 0.0 && peak_hold.is_finite()) => {
+                Err("peak_hold must be positive".into())
+            }
+            Self {
+                quantize: Some(Quantize::Levels(levels)),
+                ..
+            } if !(2..=1000).contains(levels) => {
+                Err("quantize must be from 2 to 1000 levels".into())
+            }
+            Self {
+                dither: Some(dither),
+                ..
+            } if !(0.0..=0.5).contains(dither) => {
+                Err("dither must be from 0 to 0.5".into())
+            }
+            Self {
+                pulse: Some(pulse), ..
+            } if !(10.0..=5000.0).contains(pulse) => {
+                Err("pulse must be from 10 to 5000 ms".into())
+            }
+            Self {
+                alternate: Some(levels),
+                ..
+            } if !levels.iter().all(|level| (0.0..=1.0).contains(level)) => {
+                Err("alternate must be two values from 0 to 1".into())
+            }
+            Self {
+                alternate_hz: Some(hz),
+                ..
+            } if !(*hz > 0.0 && *hz <= 50.0) => {
+                Err("alternate_hz must be above 0 and at most 50".into())
+            }
+            Self {
+                release: Some(release),
+                ..
+            } if !(*release > 0.0 && release.is_finite()) => {
+                Err("release must be positive".into())
+            }
+            Self {
+                floor: Some(floor), ..
+            } if !(0.0..1.0).contains(floor) => {
+                Err("floor must be at least 0 and below 1".into())
+            }
+            Self {
+                deadband: Some(deadband),
+                ..
+            } if !(0.0..0.5).contains(deadband) => {
+                Err("deadband must be at least 0 and below 0.5".into())
+            }
+            Self {
+                range: Some([min, max]),
+                ..
+            } if !(min < max && min.is_finite() && max.is_finite()) => {
+                Err("range must go from a lower to a higher value".into())
+            }
+            Self {
+                log_floor: Some(log_floor),
+                ..
+            } if !(*log_floor > 0.0 && *log_floor < 1.0) => {
+                Err("log_floor must be above 0 and below 1".into())
+            }
+            Self {
+                phase: Some(phase), ..
+            } if !(0.0..1.0).contains(phase) => {
+                Err("phase must be at least 0 and below 1".into())
+            }
+            Self {
+                colors: Some(colors),
+                ..
+            } if colors.is_empty() => Err("colors can't be empty".into()),
+            Self {
+                threshold: Some(threshold),
+                ..
+            } if !(*threshold > 0.0 && *threshold <= 1.0) => {
+                Err("threshold must be above 0 and at most 1".into())
+            }
+            _ => {
+                self.curve.as_ref().map_or(Ok(()), Curve::validate)?;
+                self.change.as_ref().map_or(Ok(()), Change::validate)?;
+                self.alert.as_ref().map_or(Ok(()), Alert::validate)
+            }
+        }
+    }
+
+    /// Fills in whatever this pad doesn't set from `defaults`.
+    ///
+    /// Metrics that are `discrete` snap to new values, unless the pad
+    /// itself asks for smoothing.
+    #[must_use]
+    pub fn or(self, defaults: &Self, discrete: bool) -> Self {
+        let default_smoothing = if discrete {
+            1.0
+        } else {
+            defaults.smoothing.unwrap_or(DEFAULT_SMOOTHING)
+        };
+        Self {
+            curve: self.curve.or_else(|| defaults.curve.clone()),
+            smoothing: Some(self.smoothing.unwrap_or(default_smoothing)),
+            smoothed: None,
+            hysteresis: self.hysteresis.or(defaults.hysteresis),
+            lit: Schmitt::default(),
+            full: Schmitt::default(),
+            period: self.period.or(defaults.period),
+            quantize: self.quantize.or(defaults.quantize),
+            dither: self.dither.or(defaults.dither),
+            dither_seed: self.dither_seed.or(defaults.dither_seed),
+            phase: self.phase.or(defaults.phase),
+            threshold: self.threshold.or(defaults.threshold),
+            above: Schmitt::default(),
+            alert: self.alert.or_else(|| defaults.alert.clone()),
+            change: self.change.or_else(|| defaults.change.clone()),
+            mode: self.mode.or(defaults.mode),
+            pulse: self.pulse.or(defaults.pulse),
+            events_seen: None,
+            alternate: self.alternate.or(defaults.alternate),
+            alternate_hz: self.alternate_hz.or(defaults.alternate_hz),
+            peak_hold: self.peak_hold.or(defaults.peak_hold),
+            peak: None,
+            release: self.release.or(defaults.release),
+            released: None,
+            floor: self.floor.or(defaults.floor),
+            deadband: self.deadband.or(defaults.deadband),
+            invert: self.invert.or(defaults.invert),
+            range: self.range.or(defaults.range),
+            scaling: self.scaling.or(defaults.scaling),
+            log_floor: self.log_floor.or(defaults.log_floor),
+            colors: self.colors.or_else(|| defaults.colors.clone()),
+            shown: 0.0,
+        }
+    }
+
+    /// Length of one on/off cycle of the pad.
+    pub fn period(&self) -> Duration {
+        Duration::from_secs_f32(self.period.unwrap_or(DEFAULT_PERIOD) / 1000.0)
+    }
+
+    /// Number of duty cycles to round to, if any.
+    pub const fn levels(&self) -> Option<u32> {
+        match self.quantize {
+            Some(Quantize::Levels(levels)) => Some(levels),
+            Some(Quantize::On(true)) => Some(DEFAULT_LEVELS),
+            Some(Quantize::On(false)) | None => None,
+        }
+    }
+
+    /// Dithering for `pad`'s cycles, if any. Each pad gets its own jitter,
+    /// even from the same seed.
+    pub fn dither(&self, pad: Pad) -> Option<Dither> {
+        let index = PADS.iter().position(|&other| other == pad).unwrap_or(0);
+        let seed = self.dither_seed.map(|seed| {
+            seed ^ (index as u64 + 1).wrapping_mul(0xD1B5_4A32_D192_ED03)
+        });
+        self.dither
+            .filter(|&dither| dither > 0.0)
+            .map(|dither| Dither::new(dither, seed))
+    }
+
+    /// Whether a pad's worker, which settles its period, quantizing,
+    /// dithering and phase once, at startup, would run the same way with
+    /// `other`.
+    pub fn runs_like(&self, other: &Self) -> bool {
+        self.period() == other.period()
+            && self.levels() == other.levels()
+            && self.dither == other.dither
+            && self.dither_seed == other.dither_seed
+            && self.phase == other.phase
+    }
+
+    /// How long after the others `pad` starts its cycles.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn phase_offset(&self, pad: Pad) -> Duration {
+        let phase = self.phase.unwrap_or_else(|| {
+            let index = PADS.iter().position(|&other| other == pad);
+            index.unwrap_or(0) as f32 / PADS.len() as f32
+        });
+        self.period().mul_f32(phase)
+    }
+
+    /// Whether the pad has an alert that needs acknowledging.
+    pub fn latches(&self) -> bool {
+        self.alert.as_ref().is_some_and(|alert| alert.latch)
+    }
+
+    /// Whether the pad's alert is latched on.
+    pub fn latched(&self) -> bool {
+        self.alert.as_ref().is_some_and(|alert| alert.latched)
+    }
+
+    /// Lets a latched alert go. Returns whether there was one.
+    pub fn acknowledge(&mut self) -> bool {
+        self.alert
+            .as_mut()
+            .is_some_and(|alert| std::mem::take(&mut alert.latched))
+    }
+
+    /// Whether the pad flashes for events rather than showing a value.
+    pub const fn pulses(&self) -> bool {
+        matches!(self.mode, Some(Mode::Pulse))
+    }
+
+    /// Turns a value sampled from the metric, and the number of `events`
+    /// it's counted so far if it counts them, into what each of
+    /// `segments` pads shows. With more than one, they make up a bar
+    /// graph: each covers the next slice of the range, lit fully once the
+    /// value is past its slice.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn apply(
+        &mut self,
+        value: f32,
+        events: Option<u64>,
+        segments: usize,
+    ) -> Vec<Show> {
+        let new_events = events.map_or(0, |total| {
+            let seen = self.events_seen.replace(total).unwrap_or(total);
+            u32::try_from(total.saturating_sub(seen)).unwrap_or(u32::MAX)
+        });
+        // Everything else, alerts included, goes by the change instead.
+        let value = self
+            .change
+            .as_mut()
+            .map_or(value, |change| change.update(value));
+        let delta = self.hysteresis.unwrap_or(DEFAULT_HYSTERESIS);
+        // Worked out even while an alert hides it, so the moving average
+        // doesn't jump when the alert clears.
+        let mut level = self.level(value, delta);
+        if self.invert == Some(true) {
+            level = 1.0 - level;
+        }
+        self.shown = level;
+        if let Some(alert) = &mut self.alert {
+            if alert.update(value, delta) {
+                return vec![alert.show(); segments];
+            }
+        }
+        match self.mode.unwrap_or_default() {
+            Mode::Steady => (0..segments)
+                .map(|segment| {
+                    let level = level
+                        .mul_add(segments as f32, -(segment as f32))
+                        .clamp(0.0, 1.0);
+                    // A threshold gives exactly 0 or 1, so pwm never has
+                    // to switch the pad mid-cycle; no curve should change
+                    // that.
+                    if self.threshold.is_some() {
+                        Show::Level(level)
+                    } else {
+                        Show::Level(self.curve(level))
+                    }
+                })
+                .collect(),
+            Mode::Breathe => vec![
+                Show::Breathe {
+                    hz: 1.0
+                        / (FASTEST_BREATH - SLOWEST_BREATH)
+                            .mul_add(level, SLOWEST_BREATH),
+                };
+                segments
+            ],
+            Mode::Pulse => vec![
+                Show::Pulse {
+                    events: new_events,
+                    length: Duration::from_secs_f32(
+                        self.pulse.unwrap_or(DEFAULT_PULSE) / 1000.0,
+                    ),
+                };
+                segments
+            ],
+            // On from halfway, since these metrics only ever read 0 or 1.
+            Mode::Alternate if level < 0.5 => {
+                vec![Show::Level(0.0); segments]
+            }
+            Mode::Alternate => {
+                let [low, high] = self.alternate.unwrap_or(DEFAULT_ALTERNATE);
+                vec![
+                    Show::Alternate {
+                        low,
+                        high,
+                        hz: self.alternate_hz.unwrap_or(DEFAULT_ALTERNATE_HZ),
+                    };
+                    segments
+                ]
+            }
+        }
+    }
+
+    /// The last value the pad was given, from 0 to 1, after all the
+    /// settings that change it.
+    pub const fn shown(&self) -> f32 { self.shown }
+
+    /// Color of the pad for the last value it was given.
+    pub fn color(&self) -> Rgb {
+        color::gradient(
+            self.colors.as_deref().unwrap_or(&DEFAULT_GRADIENT),
+            self.shown,
+        )
+    }
+
+    /// The value, remapped, scaled, smoothed, slowed down on the way down
+    /// and settled, or 0 or 1 with a threshold.
+    fn level(&mut self, value: f32, delta: f32) -> f32 {
+        let value = self.range.map_or(value, |[min, max]| {
+            ((value - min) / (max - min)).clamp(0.0, 1.0)
+        });
+        let value = self
+            .scaling
+            .unwrap_or_default()
+            .apply(value, self.log_floor.unwrap_or(DEFAULT_LOG_FLOOR));
+        let value = self.smooth(value);
+        let now = Instant::now();
+        let value = self.fall_slowly(value, now);
+        let value = self.hold_peak(value, now);
+        match self.threshold {
+            Some(threshold) => {
+                if self.above.update(value, threshold, delta) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            None => self.settle(value, delta),
+        }
+    }
+
+    fn curve(&self, level: f32) -> f32 {
+        if level < self.deadband.unwrap_or(DEFAULT_DEADBAND) {
+            return 0.0;
+        }
+        let duty_cycle = self.curve.as_ref().map_or_else(
+            || Curve::Gamma(DEFAULT_GAMMA).apply(level),
+            |curve| curve.apply(level),
+        );
+        match self.floor {
+            Some(floor) if level > 0.0 => duty_cycle.max(floor),
+            _ => duty_cycle,
+        }
+    }
+
+    /// Keeps values hovering right above 0 or right below 1 from making
+    /// the pad flicker between off and barely on, or full and nearly full.
+    fn settle(&mut self, value: f32, delta: f32) -> f32 {
+        if !self.lit.update(value, delta, delta) {
+            0.0
+        } else if self.full.update(value, 1.0, delta) {
+            1.0
+        } else {
+            value
+        }
+    }
+
+    /// `value`, or wherever the last one has fallen to by `now` if that's
+    /// still higher.
+    fn fall_slowly(&mut self, value: f32, now: Instant) -> f32 {
+        let Some(rate) = self.release else {
+            return value;
+        };
+        let shown = self.released.map_or(value, |(previous, at)| {
+            let fallen = now.duration_since(at).as_secs_f32() * rate;
+            (previous - fallen).max(value)
+        });
+        self.released = Some((shown, now));
+        shown
+    }
+
+    /// The higher of `value` and the latest peak, which fades linearly
+    /// from its height to 0 over `peak_hold` seconds, as of `now`.
+    fn hold_peak(&mut self, value: f32, now: Instant) -> f32 {
+        let Some(window) = self.peak_hold else {
+            return value;
+        };
+        let held = self.peak.map_or(0.0, |(peak, reached)| {
+            let faded = now.duration_since(reached).as_secs_f32() / window;
+            peak * (1.0 - faded).max(0.0)
+        });
+        if value >= held {
+            self.peak = Some((value, now));
+            value
+        } else {
+            held
+        }
+    }
+
+    fn smooth(&mut self, value: f32) -> f32 {
+        let alpha = self.smoothing.unwrap_or(DEFAULT_SMOOTHING);
+        if alpha >= 1.0 {
+            return value;
+        }
+        let smoothed = self.smoothed.map_or(value, |previous| {
+            alpha.mul_add(value - previous, previous)
+        });
+        self.smoothed = Some(smoothed);
+        smoothed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "{} isn't {}",
+            actual,
+            expected
+        );
+    }
+
+    /// A pad's style as written in the config, with the defaults for a
+    /// metric that isn't discrete filled in.
+    fn style(config: &str) -> Style {
+        toml::from_str::<Style>(config)
+            .unwrap()
+            .or(&Style::default(), false)
+    }
+
+    /// What a single pad shows for `value`, as a duty cycle.
+    fn shown_level(style: &mut Style, value: f32) -> f32 {
+        match style.apply(value, None, 1).as_slice() {
+            [Show::Level(level)] => *level,
+            _ => panic!("not a steady level"),
+        }
+    }
+
+    /// Values from 0 to 1 in steps of 0.01.
+    fn values() -> impl Iterator<Item = f32> {
+        (0..=100u8).map(|step| f32::from(step) / 100.0)
+    }
+
+    #[test]
+    fn gamma_is_monotonic() {
+        for gamma in [0.5, 1.0, 2.0, 2.2, 4.0] {
+            let curve = Curve::Gamma(gamma);
+            let duty_cycles: Vec<f32> =
+                values().map(|value| curve.apply(value)).collect();
+            assert!(
+                duty_cycles.windows(2).all(|pair| pair[0] <= pair[1]),
+                "gamma {} decreases",
+                gamma
+            );
+        }
+    }
+
+    #[test]
+    fn gamma_clamps_to_the_unit_range() {
+        for gamma in [0.5, 1.0, 2.2] {
+            let curve = Curve::Gamma(gamma);
+            assert_near(curve.apply(-0.5), 0.0);
+            assert_near(curve.apply(1.5), 1.0);
+            assert_near(curve.apply(f32::INFINITY), 1.0);
+            for value in values() {
+                assert!((0.0..=1.0).contains(&curve.apply(value)));
+            }
+        }
+    }
+
+    #[test]
+    fn gamma_of_one_is_the_identity() {
+        let curve = Curve::Gamma(1.0);
+        for value in values() {
+            assert_near(curve.apply(value), value);
+        }
+    }
+
+    #[test]
+    fn tables_interpolate_between_their_points() {
+        let curve = Curve::Table(vec![0.0, 0.1, 1.0]);
+        assert_near(curve.apply(0.0), 0.0);
+        assert_near(curve.apply(0.25), 0.05);
+        assert_near(curve.apply(0.5), 0.1);
+        assert_near(curve.apply(0.75), 0.55);
+        assert_near(curve.apply(1.0), 1.0);
+        assert_near(curve.apply(2.0), 1.0);
+    }
+
+    #[test]
+    fn smoothing_converges_on_a_steady_value() {
+        let mut style = style("smoothing = 0.3");
+        assert_near(style.smooth(0.0), 0.0);
+        let mut previous = 0.0;
+        for step in 1..=30 {
+            let smoothed = style.smooth(1.0);
+            assert!(smoothed > previous && smoothed <= 1.0);
+            assert_near(smoothed, 1.0 - 0.7f32.powi(step));
+            previous = smoothed;
+        }
+        assert!(1.0 - previous < 1e-4);
+    }
+
+    #[test]
+    fn smoothing_starts_from_the_first_value() {
+        let mut style = style("smoothing = 0.3");
+        assert_near(style.smooth(0.8), 0.8);
+    }
+
+    #[test]
+    fn smoothing_of_one_passes_values_through() {
+        let mut style = style("smoothing = 1.0");
+        for value in [0.0, 1.0, 0.2, 0.9, 0.0] {
+            assert_near(style.smooth(value), value);
+        }
+    }
+
+    #[test]
+    fn discrete_metrics_pass_values_through() {
+        let mut style = Style::default().or(&style("smoothing = 0.3"), true);
+        for value in [0.0, 1.0, 0.0] {
+            assert_near(style.smooth(value), value);
+        }
+    }
+
+    #[test]
+    fn smoothing_of_zero_is_rejected() {
+        // It would freeze the pad at its first value.
+        assert!(style("smoothing = 0.0").validate().is_err());
+        assert!(style("smoothing = 1.0").validate().is_ok());
+    }
+
+    /// How often `outputs` changes from one value to the next.
+    fn changes(outputs: &[f32]) -> usize {
+        outputs
+            .windows(2)
+            .filter(|pair| (pair[0] - pair[1]).abs() > f32::EPSILON)
+            .count()
+    }
+
+    #[test]
+    fn oscillating_across_a_boundary_switches_once() {
+        let mut schmitt = Schmitt::default();
+        let outputs: Vec<f32> = [0.4, 0.49, 0.51, 0.49, 0.51, 0.48, 0.5, 0.49]
+            .iter()
+            .map(|&value| {
+                f32::from(u8::from(schmitt.update(value, 0.5, 0.03)))
+            })
+            .collect();
+        assert_eq!(outputs, [0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+        // Only dropping clearly below the boundary switches it back.
+        assert!(!schmitt.update(0.47, 0.5, 0.03));
+        assert!(!schmitt.update(0.49, 0.5, 0.03));
+    }
+
+    #[test]
+    fn values_hovering_near_zero_dont_flicker() {
+        let mut style = style("smoothing = 1.0\nhysteresis = 0.03");
+        let outputs: Vec<f32> = [0.0, 0.02, 0.04, 0.01, 0.04, 0.01, 0.02]
+            .iter()
+            .map(|&value| style.level(value, 0.03))
+            .map(|level| f32::from(u8::from(level > 0.0)))
+            .collect();
+        assert_eq!(changes(&outputs), 1);
+    }
+
+    #[test]
+    fn values_hovering_near_full_dont_flicker() {
+        let mut style = style("smoothing = 1.0\nhysteresis = 0.03");
+        let outputs: Vec<f32> = [0.9, 1.0, 0.99, 1.0, 0.98, 1.0, 0.99]
+            .iter()
+            .map(|&value| style.level(value, 0.03))
+            .collect();
+        assert_eq!(changes(&outputs), 1);
+        assert_near(style.level(0.96, 0.03), 0.96);
+    }
+
+    #[test]
+    fn value_at_the_threshold_is_on() {
+        let mut style = style("smoothing = 1.0\nthreshold = 0.5");
+        assert_near(shown_level(&mut style, 0.5), 1.0);
+    }
+
+    #[test]
+    fn value_just_below_the_threshold_is_off() {
+        let mut style = style("smoothing = 1.0\nthreshold = 0.5");
+        assert_near(shown_level(&mut style, 0.499), 0.0);
+    }
+
+    #[test]
+    fn thresholds_show_only_off_or_on() {
+        let mut style = style("smoothing = 1.0\nthreshold = 0.5\nfloor = 0.1");
+        for value in values() {
+            let level = shown_level(&mut style, value);
+            assert!([0.0, 1.0].contains(&level), "{} at {}", level, value);
+        }
+    }
+
+    #[test]
+    fn thresholds_switch_off_past_the_hysteresis() {
+        let mut style =
+            style("smoothing = 1.0\nthreshold = 0.5\nhysteresis = 0.05");
+        let levels: Vec<f32> = [0.5, 0.46, 0.5, 0.451, 0.45, 0.49, 0.5]
+            .iter()
+            .map(|&value| shown_level(&mut style, value))
+            .collect();
+        assert_eq!(levels, [1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn peaks_decay_linearly_over_the_hold() {
+        let mut style = style("peak_hold = 2.0");
+        let start = Instant::now();
+        assert_near(style.hold_peak(1.0, start), 1.0);
+        // Sampled every 100 ms, the peak loses a twentieth each time.
+        let mut previous = 1.0;
+        for sample in 1..=20u32 {
+            let now = start + Duration::from_millis(100) * sample;
+            let held = style.hold_peak(0.0, now);
+            assert_near(previous - held, 0.05);
+            previous = held;
+        }
+        assert_near(style.hold_peak(0.0, start + Duration::from_secs(3)), 0.0);
+    }
+
+    #[test]
+    fn peaks_give_way_to_higher_values() {
+        let mut style = style("peak_hold = 2.0");
+        let start = Instant::now();
+        style.hold_peak(0.6, start);
+        let later = start + Duration::from_secs(1);
+        assert_near(style.hold_peak(0.2, later), 0.3);
+        assert_near(style.hold_peak(0.5, later), 0.5);
+    }
+
+    #[test]
+    fn steady_values_pass_through_peak_hold() {
+        let mut style = style("peak_hold = 2.0");
+        let start = Instant::now();
+        for sample in 0..50u32 {
+            let now = start + Duration::from_millis(100) * sample;
+            assert_near(style.hold_peak(0.4, now), 0.4);
+        }
+    }
+
+    #[test]
+    fn floor_leaves_zero_dark() {
+        let style = style("floor = 0.05\ndeadband = 0.0");
+        assert_near(style.curve(0.0), 0.0);
+    }
+
+    #[test]
+    fn floor_lifts_values_just_above_zero() {
+        let style = style("floor = 0.05\ndeadband = 0.0");
+        assert_near(style.curve(f32::EPSILON), 0.05);
+        assert_near(style.curve(0.1), 0.05);
+    }
+
+    #[test]
+    fn floor_leaves_full_brightness_alone() {
+        let style = style("floor = 0.05\ndeadband = 0.0");
+        assert_near(style.curve(1.0), 1.0);
+    }
+
+    #[test]
+    fn floor_applies_after_the_curve() {
+        // 0.3 squared is 0.09, which is above the floor, and 0.2 squared
+        // is 0.04, which isn't.
+        let style = style("floor = 0.05\ndeadband = 0.0\ncurve = 2.0");
+        assert_near(style.curve(0.3), 0.09);
+        assert_near(style.curve(0.2), 0.05);
+    }
+
+    /// A pad lighting up as its value drops, with an alert for when the
+    /// value itself is high.
+    fn inverted_with_alert() -> Style {
+        style(
+            "smoothing = 1.0
+            invert = true
+            curve = 1.0
+            alert = { above = 0.9 }",
+        )
+    }
+
+    #[test]
+    fn alerts_go_by_the_value_before_inversion() {
+        let mut style = inverted_with_alert();
+        // Nearly dark once inverted, but the alert still sees 0.95.
+        let shows = style.apply(0.95, None, 1);
+        assert!(matches!(shows.as_slice(), [Show::Blink { .. }]));
+    }
+
+    #[test]
+    fn inverted_brightness_doesnt_raise_alerts() {
+        let mut style = inverted_with_alert();
+        // Nearly full once inverted, but the value is far from the alert.
+        assert_near(shown_level(&mut style, 0.05), 0.95);
+    }
+
+    #[test]
+    fn inversion_applies_to_what_is_shown() {
+        let mut style = style("smoothing = 1.0\ninvert = true\ncurve = 1.0");
+        assert_near(shown_level(&mut style, 0.3), 0.7);
+        assert_near(style.shown(), 0.7);
+    }
+
+    #[test]
+    fn log_scaling_reads_zero_at_and_below_the_floor() {
+        let floor = 0.001;
+        assert_near(Scaling::Log.apply(0.0, floor), 0.0);
+        assert_near(Scaling::Log.apply(floor / 10.0, floor), 0.0);
+        assert_near(Scaling::Log.apply(floor, floor), 0.0);
+        assert_near(Scaling::Log.apply(-1.0, floor), 0.0);
+    }
+
+    #[test]
+    fn log_scaling_reads_one_at_the_top() {
+        assert_near(Scaling::Log.apply(1.0, 0.001), 1.0);
+        assert_near(Scaling::Log.apply(10.0, 0.001), 1.0);
+    }
+
+    #[test]
+    fn log_scaling_gives_each_decade_the_same_share() {
+        let floor = 0.001;
+        assert_near(Scaling::Log.apply(0.01, floor), 1.0 / 3.0);
+        assert_near(Scaling::Log.apply(0.1, floor), 2.0 / 3.0);
+    }
+
+    #[test]
+    fn log_scaling_spans_the_range() {
+        // 100 kB/s to 1 GB/s, four decades.
+        let mut style = style(
+            "smoothing = 1.0
+            range = [0.0, 1e9]
+            scaling = \"log\"
+            log_floor = 1e-4",
+        );
+        assert_near(style.level(1e5, 0.0), 0.0);
+        assert_near(style.level(1e7, 0.0), 0.5);
+        assert_near(style.level(1e9, 0.0), 1.0);
+    }
+
+    #[test]
+    fn linear_scaling_passes_values_through() {
+        for value in values() {
+            assert_near(Scaling::Linear.apply(value, 0.001), value);
+        }
+    }
+
+    #[test]
+    fn release_ramps_down_after_a_step() {
+        // 200% per second, sampled every 50 ms: a tenth per sample.
+        let mut style = style("release = 2.0");
+        let start = Instant::now();
+        assert_near(style.fall_slowly(1.0, start), 1.0);
+        let ramp: Vec<f32> = (1..=10u32)
+            .map(|sample| {
+                let now = start + Duration::from_millis(50) * sample;
+                style.fall_slowly(0.2, now)
+            })
+            .collect();
+        let expected = [0.9, 0.8, 0.7, 0.6, 0.5, 0.4, 0.3, 0.2, 0.2, 0.2];
+        for (shown, expected) in ramp.iter().zip(expected) {
+            assert_near(*shown, expected);
+        }
+    }
+
+    #[test]
+    fn release_attacks_at_once() {
+        let mut style = style("release = 2.0");
+        let start = Instant::now();
+        style.fall_slowly(0.1, start);
+        let now = start + Duration::from_millis(50);
+        assert_near(style.fall_slowly(0.9, now), 0.9);
+    }
+
+    #[test]
+    fn deadband_shows_values_from_it_up() {
+        let style = style("deadband = 0.02\ncurve = 1.0");
+        assert_near(style.curve(0.02), 0.02);
+        assert_near(style.curve(0.021), 0.021);
+    }
+
+    #[test]
+    fn deadband_darkens_values_below_it() {
+        let style = style("deadband = 0.02\ncurve = 1.0");
+        assert_near(style.curve(0.019), 0.0);
+        assert_near(style.curve(f32::EPSILON), 0.0);
+    }
+
+    #[test]
+    fn deadband_wins_over_the_floor_below_it() {
+        let style = style("deadband = 0.02\nfloor = 0.1\ncurve = 1.0");
+        assert_near(style.curve(0.019), 0.0);
+        assert_near(style.curve(0.02), 0.1);
+        assert_near(style.curve(0.5), 0.5);
+    }
+
+    #[test]
+    fn deadband_leaves_alerts_the_raw_value() {
+        let mut style = style(
+            "smoothing = 1.0
+            deadband = 0.3
+            alert = { above = 0.25 }",
+        );
+        let shows = style.apply(0.26, None, 1);
+        assert!(matches!(shows.as_slice(), [Show::Blink { .. }]));
+    }
+}
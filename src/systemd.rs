@@ -0,0 +1,68 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Service state, queried through `systemctl`.
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::command;
+
+/// How long `systemctl` may take to answer.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether the machine was booted with systemd, the same way
+/// `sd_booted(3)` checks.
+pub fn booted() -> bool { Path::new("/run/systemd/system").is_dir() }
+
+/// How badly `unit` is doing: 0 while it's active, 0.5 while it changes
+/// state, and 1 when it's failed, stopped, or `systemctl` couldn't tell.
+pub fn unit_state(unit: &str) -> f32 {
+    // is-active exits non-zero for anything but "active", but still prints
+    // the state, so the exit status isn't interesting.
+    let state = command::output_with_status(
+        Command::new("systemctl").args(["is-active", unit]),
+        TIMEOUT,
+    )
+    .map(|(_, output)| output)
+    .unwrap_or_default();
+    match state.trim() {
+        "active" => 0.0,
+        "activating" | "deactivating" | "reloading" | "refreshing" => 0.5,
+        _ => 1.0,
+    }
+}
+
+/// Number of units in the failed state, or `None` if `systemctl` couldn't
+/// be asked.
+pub fn failed_units() -> Option<usize> {
+    let output = command::output(
+        Command::new("systemctl").args([
+            "list-units",
+            "--state=failed",
+            "--no-legend",
+            "--plain",
+        ]),
+        TIMEOUT,
+    )?;
+    Some(
+        output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .count(),
+    )
+}
+
+/// A `journalctl` that prints each new entry of `priority` or more severe
+/// as it arrives, one line per entry.
+pub fn follow_journal(priority: &str) -> Command {
+    let mut command = Command::new("journalctl");
+    // JSON output puts each entry on one line, even if its message spans
+    // several.
+    command
+        .args(["--follow", "--lines=0", "--output=json", "--priority"])
+        .arg(priority);
+    command
+}
@@ -0,0 +1,34 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Restarting countdown timers with `SIGUSR1`.
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static RESETS: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(unix)]
+extern "C" fn on_reset(_signal: libc::c_int) {
+    RESETS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Restarts every timer pad whenever the process gets `SIGUSR1`, e.g. from
+/// `pkill -USR1 lpd8_monitor`.
+#[cfg(unix)]
+pub fn listen_for_resets() {
+    // SAFETY: the handler only bumps an atomic counter, which is
+    // async-signal-safe.
+    unsafe {
+        libc::signal(
+            libc::SIGUSR1,
+            on_reset as *const () as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(not(unix))]
+pub const fn listen_for_resets() {}
+
+/// How often the timers have been restarted so far.
+pub fn resets() -> usize { RESETS.load(Ordering::Relaxed) }
@@ -0,0 +1,68 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! How far off the system clock is, according to the NTP daemon keeping it
+//! in sync.
+use std::process::Command;
+use std::time::Duration;
+
+use crate::command;
+
+/// Offset of the system clock from NTP time, in milliseconds either way,
+/// or `None` if there's no daemon or it hasn't got a source to sync to.
+///
+/// Asks chrony first, then systemd-timesyncd.
+pub fn offset_ms(timeout: Duration) -> Option<f32> {
+    command::output(Command::new("chronyc").arg("tracking"), timeout)
+        .map_or_else(
+            || {
+                command::output(
+                    Command::new("timedatectl").arg("timesync-status"),
+                    timeout,
+                )
+                .and_then(|status| parse_timesyncd(&status))
+            },
+            |tracking| parse_chrony(&tracking),
+        )
+}
+
+/// Picks the offset out of `chronyc tracking`, which looks like
+/// `System time : 0.000012 seconds fast of NTP time`.
+fn parse_chrony(tracking: &str) -> Option<f32> {
+    let field = |name: &str| {
+        tracking.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim() == name).then(|| value.trim())
+        })
+    };
+    // chrony reports a reference ID of all zeroes until it has a source.
+    let reference = field("Reference ID")?;
+    if reference.starts_with("00000000") {
+        return None;
+    }
+    let seconds: f32 =
+        field("System time")?.split(' ').next()?.parse().ok()?;
+    Some(seconds.abs() * 1000.0)
+}
+
+/// Picks the offset out of `timedatectl timesync-status`, which looks like
+/// `Offset: -1.234ms`, and is missing until there's been an answer.
+fn parse_timesyncd(status: &str) -> Option<f32> {
+    let offset = status
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Offset:").map(str::trim))?;
+    let offset = offset.trim_start_matches(['+', '-']);
+    let unit_start = offset.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let (value, unit) = offset.split_at(unit_start);
+    let value: f32 = value.parse().ok()?;
+    let scale = match unit {
+        "us" | "µs" => 0.001,
+        "ms" => 1.0,
+        "s" => 1000.0,
+        "min" => 60_000.0,
+        _ => return None,
+    };
+    Some(value * scale)
+}
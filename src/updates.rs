@@ -0,0 +1,137 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Counting pending package updates, by asking the package manager.
+use std::process::Command;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::command;
+
+/// The package manager to ask.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// `checkupdates`, from pacman-contrib, which syncs a separate copy of
+    /// the databases so it doesn't need root.
+    Pacman,
+    /// `apt list --upgradable`. That only looks at the package lists as of
+    /// the last `apt update`, which most distributions run on a timer.
+    Apt,
+    /// `dnf check-update`.
+    Dnf,
+}
+
+/// Number of packages with an update available, or `None` if the package
+/// manager couldn't tell.
+pub fn pending(backend: Backend, timeout: Duration) -> Option<usize> {
+    match backend {
+        Backend::Pacman => {
+            let (status, output) = command::output_with_status(
+                &mut Command::new("checkupdates"),
+                timeout,
+            )?;
+            // Exits with 2 when everything's up to date.
+            match status.code()? {
+                0 => Some(parse_pacman(&output)),
+                2 => Some(0),
+                _ => None,
+            }
+        }
+        Backend::Apt => command::output(
+            Command::new("apt").args(["list", "--upgradable"]),
+            timeout,
+        )
+        .map(|output| parse_apt(&output)),
+        Backend::Dnf => {
+            let (status, output) = command::output_with_status(
+                Command::new("dnf").args(["check-update", "--quiet"]),
+                timeout,
+            )?;
+            // Exits with 100 when there are updates.
+            match status.code()? {
+                0 => Some(0),
+                100 => Some(parse_dnf(&output)),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// `checkupdates` prints one `name 1.0-1 -> 1.1-1` line per package.
+fn parse_pacman(output: &str) -> usize {
+    output.lines().filter(|line| line.contains(" -> ")).count()
+}
+
+/// `apt list` prints a `Listing...` header, then one
+/// `name/suite 1.1 amd64 [upgradable from: 1.0]` line per package.
+fn parse_apt(output: &str) -> usize {
+    output
+        .lines()
+        .filter(|line| line.contains("[upgradable from:"))
+        .count()
+}
+
+/// `dnf check-update` prints one `name.arch 1.1-1.fc38 repository` line
+/// per package, and then possibly the packages they obsolete, which are
+/// already counted.
+fn parse_dnf(output: &str) -> usize {
+    output
+        .lines()
+        .take_while(|line| !line.starts_with("Obsoleting"))
+        .filter(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            fields.len() == 3 && fields[0].contains('.')
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_checkupdates_output() {
+        let output = "\
+linux 6.5.9.arch2-1 -> 6.6.1.arch1-1
+linux-headers 6.5.9.arch2-1 -> 6.6.1.arch1-1
+mesa 1:23.2.1-1 -> 1:23.2.1-2
+python-urllib3 1.26.18-1 -> 2.0.7-1
+";
+        assert_eq!(parse_pacman(output), 4);
+        assert_eq!(parse_pacman(""), 0);
+    }
+
+    #[test]
+    fn counts_apt_list_output() {
+        let output = "\
+Listing... Done
+curl/jammy-updates,jammy-security 7.81.0-1ubuntu1.15 amd64 [upgradable from: \
+                      7.81.0-1ubuntu1.14]
+libcurl4/jammy-updates,jammy-security 7.81.0-1ubuntu1.15 amd64 [upgradable \
+                      from: 7.81.0-1ubuntu1.14]
+tzdata/jammy-updates 2023c-0ubuntu0.22.04.2 all [upgradable from: \
+                      2023c-0ubuntu0.22.04.1]
+";
+        assert_eq!(parse_apt(output), 3);
+        assert_eq!(parse_apt("Listing... Done\n"), 0);
+    }
+
+    #[test]
+    fn counts_dnf_check_update_output() {
+        let output = "
+firefox.x86_64                      119.0-2.fc39               updates
+kernel.x86_64                       6.5.10-300.fc39            updates
+kernel-core.x86_64                  6.5.10-300.fc39            updates
+python3-urllib3.noarch              1.26.18-1.fc39             updates
+Obsoleting Packages
+grub2-tools-efi.x86_64              1:2.06-102.fc39            updates
+    grub2-tools-efi.x86_64          1:2.06-100.fc39            @updates
+";
+        assert_eq!(parse_dnf(output), 4);
+        assert_eq!(parse_dnf(""), 0);
+    }
+}
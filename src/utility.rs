@@ -3,8 +3,20 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 //
 // Copyright 2021, Slavfox.
-use sysinfo::{ComponentExt, NetworkExt, ProcessorExt, System, SystemExt};
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::Path;
 
+use serde::Deserialize;
+use sysinfo::{
+    ComponentExt, DiskExt, NetworkExt, NetworksExt, ProcessExt, ProcessStatus,
+    ProcessorExt, RefreshKind, System, SystemExt,
+};
+
+use crate::color::Rgb;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "PadNumber")]
 pub enum Pad {
     Pad1,
     Pad2,
@@ -16,7 +28,53 @@ pub enum Pad {
     Pad8,
 }
 
-pub fn pad_to_midi_note(pad: &Pad) -> u8 {
+/// A pad's number as written in the config: a string as a table key, like
+/// `[pads.1]`, and a plain number anywhere else.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PadNumber {
+    Number(i64),
+    Key(String),
+}
+
+impl TryFrom<PadNumber> for Pad {
+    type Error = String;
+
+    fn try_from(number: PadNumber) -> Result<Self, String> {
+        let number = match number {
+            PadNumber::Number(number) => number.to_string(),
+            PadNumber::Key(key) => key,
+        };
+        number
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| PADS.get(index.checked_sub(1)?))
+            .copied()
+            .ok_or_else(|| format!("there's no pad {number}"))
+    }
+}
+
+/// One step of a piecewise mapping: from `after_hours` of uptime on, the
+/// pad shows `level`.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UptimeStep {
+    pub after_hours: f32,
+    pub level: f32,
+}
+
+pub const PADS: [Pad; 8] = [
+    Pad::Pad1,
+    Pad::Pad2,
+    Pad::Pad3,
+    Pad::Pad4,
+    Pad::Pad5,
+    Pad::Pad6,
+    Pad::Pad7,
+    Pad::Pad8,
+];
+
+pub const fn pad_to_midi_note(pad: Pad) -> u8 {
     match pad {
         Pad::Pad1 => 0x24,
         Pad::Pad2 => 0x25,
@@ -29,18 +87,198 @@ pub fn pad_to_midi_note(pad: &Pad) -> u8 {
     }
 }
 
-pub fn note_on(pad: &Pad) -> [u8; 3] { [0x90, pad_to_midi_note(pad), 0x7F] }
+/// The pad that plays `note`, if any.
+pub fn midi_note_to_pad(note: u8) -> Option<Pad> {
+    PADS.iter()
+        .copied()
+        .find(|&pad| pad_to_midi_note(pad) == note)
+}
+
+pub const fn note_on(pad: Pad) -> [u8; 3] {
+    [0x90, pad_to_midi_note(pad), 0x7F]
+}
+
+pub const fn note_off(pad: Pad) -> [u8; 3] {
+    [0x80, pad_to_midi_note(pad), 0x7F]
+}
+
+/// Control change 123, "all notes off".
+pub const fn all_notes_off() -> [u8; 3] { [0xB0, 0x7B, 0x00] }
+
+/// A system exclusive message setting the colors of all eight pads of an
+/// LPD8 MK2 at once, in pad order. Its data is 7-bit, so each component
+/// goes out as two bytes, the top bit first.
+pub fn mk2_pad_colors(colors: &[Rgb; 8]) -> Vec<u8> {
+    let mut message = vec![0xF0, 0x47, 0x7F, 0x4C, 0x06, 0x00, 0x30];
+    for color in colors {
+        for component in [color.red, color.green, color.blue] {
+            message.extend([component >> 7, component & 0x7F]);
+        }
+    }
+    message.push(0xF7);
+    message
+}
+
+/// A local date and time, to the second.
+pub struct LocalTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+#[cfg(unix)]
+extern "C" {
+    fn tzset();
+}
+
+impl LocalTime {
+    /// Rereads the time zone every time, so a change of time zone shows
+    /// right away.
+    #[cfg(unix)]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn now() -> Option<Self> {
+        // SAFETY: tzset only rereads the time zone, and localtime_r writes
+        // to the tm it's given.
+        let local = unsafe {
+            tzset();
+            let now = libc::time(std::ptr::null_mut());
+            let mut local: libc::tm = std::mem::zeroed();
+            let local_ptr = std::ptr::addr_of_mut!(local);
+            if libc::localtime_r(std::ptr::addr_of!(now), local_ptr).is_null()
+            {
+                return None;
+            }
+            local
+        };
+        Some(Self {
+            year: local.tm_year + 1900,
+            month: local.tm_mon as u32 + 1,
+            day: local.tm_mday as u32,
+            hour: local.tm_hour as u32,
+            minute: local.tm_min as u32,
+            second: local.tm_sec as u32,
+        })
+    }
 
-pub fn note_off(pad: &Pad) -> [u8; 3] { [0x80, pad_to_midi_note(pad), 0x7F] }
+    #[cfg(not(unix))]
+    pub const fn now() -> Option<Self> { None }
+}
+
+impl fmt::Display for LocalTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+}
+
+/// Matches `name` against a shell-style `pattern`, where `*` stands for any
+/// run of characters and `?` for exactly one.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[u8], name: &[u8]) -> bool {
+        match (pattern.split_first(), name.split_first()) {
+            (None, _) => name.is_empty(),
+            (Some((b'*', rest)), _) => {
+                matches(rest, name)
+                    || (!name.is_empty() && matches(pattern, &name[1..]))
+            }
+            (Some((b'?', rest)), Some((_, name_rest))) => {
+                matches(rest, name_rest)
+            }
+            (Some((expected, rest)), Some((found, name_rest)))
+                if expected == found =>
+            {
+                matches(rest, name_rest)
+            }
+            _ => false,
+        }
+    }
+    matches(pattern.as_bytes(), name.as_bytes())
+}
+
+/// Number of logical cores sysinfo knows about.
+pub fn logical_cores() -> usize {
+    System::new_with_specifics(RefreshKind::new().with_cpu())
+        .get_processors()
+        .len()
+}
+
+/// Whether the network interface `name` is picked by `interface`; `None`
+/// picks every interface.
+fn interface_matches(interface: Option<&str>, name: &str) -> bool {
+    interface.is_none_or(|pattern| glob_match(pattern, name))
+}
+
+/// Usage of the busiest core, or 0 if there are none.
+fn busiest(usage: impl IntoIterator<Item = f32>) -> f32 {
+    usage.into_iter().fold(0.0, f32::max)
+}
+
+/// Average usage of the cores, or 0 if there are none.
+#[allow(clippy::cast_precision_loss)]
+fn average(usage: &[f32]) -> f32 {
+    if usage.is_empty() {
+        return 0.0;
+    }
+    usage.iter().sum::<f32>() / usage.len() as f32
+}
+
+/// Share of `total` memory that's `used`, or 0 if there isn't any memory.
+#[allow(clippy::cast_precision_loss)]
+fn used_share(used: u64, total: u64) -> f32 {
+    if total == 0 {
+        return 0.0;
+    }
+    (used.min(total) as f32) / (total as f32)
+}
+
+/// Share of `total` memory that isn't `available`, or 0 if there isn't any
+/// memory.
+fn pressure_share(available: u64, total: u64) -> f32 {
+    used_share(total.saturating_sub(available), total)
+}
 
 pub trait Resources {
     fn get_cpu_usage_percent(&self) -> f32;
+    fn get_max_core_usage_percent(&self) -> f32;
+    fn get_cores_usage_percent(&self, cores: &[usize]) -> f32;
+    fn get_load_average_percent(&self) -> f32;
+    fn get_cpu_frequency_percent(&self, max_mhz: f32) -> f32;
+    fn get_process_cpu_percent(&self, name: &str) -> f32;
+    fn get_process_count_percent(&self, max: usize) -> f32;
+    fn get_zombie_count(&self) -> usize;
+    fn get_ssh_session_count(&self) -> usize;
     fn get_memory_usage_percent(&self) -> f32;
-    fn get_network_received_percent(&self) -> f32;
-    fn get_network_transmitted_percent(&self) -> f32;
+    fn get_memory_pressure_percent(&self) -> f32;
+    fn get_memory_usage_excluding_percent(&self, excluded_bytes: u64) -> f32;
+    fn get_swap_usage_percent(&self) -> f32;
+    fn get_uptime_percent(&self, steps: &[UptimeStep]) -> f32;
+    fn get_process_memory_percent(&self, name: &str) -> f32;
+    fn has_network_interface(&self, interface: &str) -> bool;
+    fn get_network_received_percent(&self, interface: Option<&str>) -> f32;
+    fn get_network_transmitted_percent(&self, interface: Option<&str>) -> f32;
     fn get_network_received_error_percent(&self) -> f32;
     fn get_network_transmitted_error_percent(&self) -> f32;
-    fn get_cpu_temperature_percent(&self) -> f32;
+    fn get_network_total_bytes(&self, interface: Option<&str>) -> u64;
+    fn get_network_total_errors(&self) -> u64;
+    fn get_component_temperature_percent(
+        &self,
+        matches: impl Fn(&str) -> bool,
+        max: f32,
+    ) -> Option<f32>;
+    fn get_gpu_temperature(&self) -> Option<f32>;
+    fn get_max_temperature(&self) -> Option<f32>;
+    fn get_disk_usage_percent(&self, path: &Path) -> Option<f32>;
 }
 
 impl Resources for System {
@@ -48,24 +286,178 @@ impl Resources for System {
         self.get_global_processor_info().get_cpu_usage()
     }
 
+    fn get_max_core_usage_percent(&self) -> f32 {
+        busiest(
+            self.get_processors()
+                .iter()
+                .map(ProcessorExt::get_cpu_usage),
+        )
+    }
+
+    fn get_cores_usage_percent(&self, cores: &[usize]) -> f32 {
+        let processors = self.get_processors();
+        let usage: Vec<f32> = cores
+            .iter()
+            .filter_map(|&core| processors.get(core))
+            .map(ProcessorExt::get_cpu_usage)
+            .collect();
+        average(&usage)
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn get_load_average_percent(&self) -> f32 {
+        // Load average isn't available on Windows, where sysinfo reports it
+        // as 0.
+        let cores = self.get_processors().len();
+        if cores == 0 {
+            return 0.0;
+        }
+        (self.get_load_average().one as f32 / cores as f32).clamp(0.0, 1.0)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn get_cpu_frequency_percent(&self, max_mhz: f32) -> f32 {
+        // Cores that don't report a frequency (common in VMs) read as
+        // 0 MHz, which just leaves the pad dark.
+        let processors = self.get_processors();
+        if processors.is_empty() || max_mhz <= 0.0 {
+            return 0.0;
+        }
+        let total: u64 =
+            processors.iter().map(ProcessorExt::get_frequency).sum();
+        let average = total as f32 / processors.len() as f32;
+        (average / max_mhz).clamp(0.0, 1.0)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn get_process_cpu_percent(&self, name: &str) -> f32 {
+        // Per-process usage is relative to a single core, so a process
+        // keeping every core busy reads as 100% times the core count.
+        let cores = self.get_processors().len();
+        if cores == 0 {
+            return 0.0;
+        }
+        let usage: f32 = self
+            .get_processes()
+            .values()
+            .filter(|process| process.name() == name)
+            .map(ProcessExt::cpu_usage)
+            .sum();
+        (usage / (100.0 * cores as f32)).clamp(0.0, 1.0)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn get_process_count_percent(&self, max: usize) -> f32 {
+        if max == 0 {
+            return 0.0;
+        }
+        (self.get_processes().len() as f32 / max as f32).clamp(0.0, 1.0)
+    }
+
+    fn get_zombie_count(&self) -> usize {
+        self.get_processes()
+            .values()
+            .filter(|process| {
+                matches!(process.status(), ProcessStatus::Zombie)
+            })
+            .count()
+    }
+
+    fn get_ssh_session_count(&self) -> usize {
+        // Each session has an unprivileged sshd process that renames itself
+        // to `sshd: user@pts/0`, or `sshd-session: ...` on newer OpenSSH;
+        // the privileged half is just `sshd: user [priv]`.
+        self.get_processes()
+            .values()
+            .filter(|process| {
+                process.cmd().first().is_some_and(|title| {
+                    title.starts_with("sshd") && title.contains('@')
+                })
+            })
+            .count()
+    }
+
     fn get_memory_usage_percent(&self) -> f32 {
-        (self.get_used_memory() as f32) / (self.get_total_memory() as f32)
+        used_share(self.get_used_memory(), self.get_total_memory())
+    }
+
+    /// Share of memory that isn't available to new allocations. Unlike
+    /// [`Self::get_memory_usage_percent`], page cache doesn't count.
+    fn get_memory_pressure_percent(&self) -> f32 {
+        pressure_share(self.get_available_memory(), self.get_total_memory())
+    }
+
+    /// Memory usage, not counting `excluded_bytes` that the system reports
+    /// as used but can hand back on demand, like the ZFS ARC.
+    fn get_memory_usage_excluding_percent(&self, excluded_bytes: u64) -> f32 {
+        // sysinfo counts memory in KiB.
+        let used =
+            self.get_used_memory().saturating_sub(excluded_bytes / 1024);
+        used_share(used, self.get_total_memory())
     }
 
-    fn get_network_received_percent(&self) -> f32 {
+    /// The level of the last step whose threshold the uptime has passed, or
+    /// 0 before the first one. `steps` must be sorted by `after_hours`.
+    #[allow(clippy::cast_precision_loss)]
+    fn get_uptime_percent(&self, steps: &[UptimeStep]) -> f32 {
+        let hours = self.get_uptime() as f32 / 3600.0;
+        steps
+            .iter()
+            .take_while(|step| hours >= step.after_hours)
+            .last()
+            .map_or(0.0, |step| step.level)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn get_process_memory_percent(&self, name: &str) -> f32 {
+        let total = self.get_total_memory();
+        if total == 0 {
+            return 0.0;
+        }
+        let resident: u64 = self
+            .get_processes()
+            .values()
+            .filter(|process| process.name() == name)
+            .map(ProcessExt::memory)
+            .sum();
+        // Shared pages are counted once per process, so the sum can end up
+        // larger than the machine's RAM.
+        ((resident as f32) / (total as f32)).clamp(0.0, 1.0)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn get_swap_usage_percent(&self) -> f32 {
+        let total = self.get_total_swap();
+        if total == 0 {
+            return 0.0;
+        }
+        (self.get_used_swap() as f32) / (total as f32)
+    }
+
+    fn has_network_interface(&self, interface: &str) -> bool {
+        self.get_networks()
+            .iter()
+            .any(|(name, _)| glob_match(interface, name))
+    }
+
+    fn get_network_received_percent(&self, interface: Option<&str>) -> f32 {
         let networks = self.get_networks();
-        for (_, network) in networks {
-            if network.get_packets_received() > 0 {
+        for (name, network) in networks {
+            if interface_matches(interface, name)
+                && network.get_packets_received() > 0
+            {
                 return 1.0;
             }
         }
         0.0
     }
 
-    fn get_network_transmitted_percent(&self) -> f32 {
+    fn get_network_transmitted_percent(&self, interface: Option<&str>) -> f32 {
         let networks = self.get_networks();
-        for (_, network) in networks {
-            if network.get_packets_transmitted() > 0 {
+        for (name, network) in networks {
+            if interface_matches(interface, name)
+                && network.get_packets_transmitted() > 0
+            {
                 return 1.0;
             }
         }
@@ -92,14 +484,137 @@ impl Resources for System {
         0.0
     }
 
-    fn get_cpu_temperature_percent(&self) -> f32 {
+    fn get_network_total_bytes(&self, interface: Option<&str>) -> u64 {
+        self.get_networks()
+            .iter()
+            .filter(|(name, _)| interface_matches(interface, name))
+            .fold(0u64, |total, (_, network)| {
+                total
+                    .wrapping_add(network.get_total_received())
+                    .wrapping_add(network.get_total_transmitted())
+            })
+    }
+
+    fn get_network_total_errors(&self) -> u64 {
+        self.get_networks()
+            .iter()
+            .fold(0u64, |total, (_, network)| {
+                total
+                    .wrapping_add(network.get_total_errors_on_received())
+                    .wrapping_add(network.get_total_errors_on_transmitted())
+            })
+    }
+
+    fn get_component_temperature_percent(
+        &self,
+        matches: impl Fn(&str) -> bool,
+        max: f32,
+    ) -> Option<f32> {
+        self.get_components()
+            .iter()
+            .find(|cmp| matches(cmp.get_label()))
+            .map(|cmp| (cmp.get_temperature() / max).clamp(0.0, 1.0))
+    }
+
+    fn get_gpu_temperature(&self) -> Option<f32> {
+        // amdgpu's hwmon sensors are called "edge" and "junction"; prefer
+        // the former, since that's what most tools report.
         let components = self.get_components();
-        (components
+        ["edge", "junction"].iter().find_map(|label| {
+            components
+                .iter()
+                .find(|cmp| cmp.get_label().contains(label))
+                .map(ComponentExt::get_temperature)
+        })
+    }
+
+    fn get_max_temperature(&self) -> Option<f32> {
+        // Broken sensors tend to read 0 or something absurd; either would
+        // win the comparison forever.
+        const PLAUSIBLE: std::ops::RangeInclusive<f32> = 0.1..=150.0;
+        self.get_components()
+            .iter()
+            .map(ComponentExt::get_temperature)
+            .filter(|temperature| PLAUSIBLE.contains(temperature))
+            .reduce(f32::max)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn get_disk_usage_percent(&self, path: &Path) -> Option<f32> {
+        // The path can be anywhere on the filesystem, so look for the
+        // innermost mount point containing it.
+        let path = path.canonicalize().ok()?;
+        let disk = self
+            .get_disks()
             .iter()
-            .find(|cmp| cmp.get_label().contains("Package id"))
-            .unwrap_or(components.first().unwrap())
-            .get_temperature()
-            / 90.0)
-            .clamp(0.0, 1.0)
+            .filter(|disk| path.starts_with(disk.get_mount_point()))
+            .max_by_key(|disk| disk.get_mount_point().as_os_str().len())?;
+        let total = disk.get_total_space();
+        if total == 0 {
+            return Some(0.0);
+        }
+        let used = total.saturating_sub(disk.get_available_space());
+        Some(used as f32 / total as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} isn't {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn busiest_core_wins_over_the_average() {
+        let usage = [0.05, 0.1, 1.0, 0.05, 0.0, 0.1, 0.05, 0.05];
+        assert_near(busiest(usage), 1.0);
+        assert_near(average(&usage), 0.175);
+    }
+
+    #[test]
+    fn single_core_is_its_own_busiest_and_average() {
+        assert_near(busiest([0.4]), 0.4);
+        assert_near(average(&[0.4]), 0.4);
+    }
+
+    #[test]
+    fn no_cores_read_as_idle() {
+        assert_near(busiest([]), 0.0);
+        assert_near(average(&[]), 0.0);
+    }
+
+    #[test]
+    fn usage_counts_page_cache_as_used() {
+        // 16 GiB, of which 4 GiB belong to processes and 10 GiB to cache.
+        let total = 16 << 20;
+        assert_near(used_share(14 << 20, total), 0.875);
+    }
+
+    #[test]
+    fn pressure_leaves_out_what_is_available() {
+        let total = 16 << 20;
+        assert_near(pressure_share(12 << 20, total), 0.25);
+        assert_near(pressure_share(total, total), 0.0);
+        assert_near(pressure_share(0, total), 1.0);
+    }
+
+    #[test]
+    fn shares_stay_within_bounds() {
+        assert_near(used_share(20, 10), 1.0);
+        assert_near(pressure_share(20, 10), 0.0);
+    }
+
+    #[test]
+    fn no_memory_reads_as_empty() {
+        assert_near(used_share(0, 0), 0.0);
+        assert_near(used_share(5, 0), 0.0);
+        assert_near(pressure_share(0, 0), 0.0);
     }
 }
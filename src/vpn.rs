@@ -0,0 +1,65 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Tunnel state, read from `/sys/class/net` and `wg`.
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 1 while `interface` exists and is up, 0 otherwise.
+///
+/// For interfaces that `wg` can inspect, a tunnel whose latest handshake is
+/// older than `stale_after` reads as 0.5: the interface stays up even when
+/// the other end is long gone.
+pub fn status(interface: &str, stale_after: Duration) -> f32 {
+    if !is_up(interface) {
+        return 0.0;
+    }
+    match handshake_age(interface) {
+        Some(age) if age > stale_after => 0.5,
+        _ => 1.0,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_up(interface: &str) -> bool {
+    use std::fs::read_to_string;
+
+    // Don't let a name like "../lo" wander off elsewhere in sysfs.
+    if interface.is_empty() || interface.contains('/') {
+        return false;
+    }
+    let device = std::path::Path::new("/sys/class/net").join(interface);
+    // tun and WireGuard devices have no carrier to speak of and report
+    // their state as "unknown" while up.
+    read_to_string(device.join("operstate"))
+        .is_ok_and(|state| matches!(state.trim(), "up" | "unknown"))
+        && read_to_string(device.join("flags")).is_ok_and(|flags| {
+            let flags = flags.trim().trim_start_matches("0x");
+            u32::from_str_radix(flags, 16).is_ok_and(|flags| flags & 1 != 0)
+        })
+}
+
+#[cfg(not(target_os = "linux"))]
+const fn is_up(_interface: &str) -> bool { false }
+
+/// Time since the most recent handshake with any peer, if `wg` knows about
+/// `interface` and it has completed one.
+fn handshake_age(interface: &str) -> Option<Duration> {
+    // Each line is `<peer public key>\t<unix time of last handshake>`, with
+    // 0 for peers that never completed one. Reading this usually needs
+    // root; without it, the tunnel is just assumed to be fresh.
+    let output = Command::new("wg")
+        .args(["show", interface, "latest-handshakes"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let latest = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1)?.parse::<u64>().ok())
+        .max()
+        .filter(|&latest| latest > 0)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    Some(now.saturating_sub(Duration::from_secs(latest)))
+}
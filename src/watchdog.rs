@@ -0,0 +1,117 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// Copyright 2021, Slavfox.
+//! Keeping an eye on the other threads, for the heartbeat pad.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+use crate::morse::Segment;
+use crate::paging;
+use crate::render::Show;
+use crate::utility::{Pad, PADS};
+
+/// How often the heartbeat checks on the other threads, and how long one
+/// beat is.
+const BEAT: Duration = Duration::from_secs(3);
+
+/// How fast the heartbeat pad blinks once something's stuck.
+const DISTRESS_HZ: f32 = 4.0;
+
+/// PWM cycles each pad's worker has been through, in pad order.
+static CYCLES: [AtomicUsize; 8] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+/// Rounds the sampler has been through.
+static SAMPLES: AtomicUsize = AtomicUsize::new(0);
+
+/// Called by `pad`'s worker after every PWM cycle.
+pub fn cycled(pad: Pad) {
+    if let Some(index) = PADS.iter().position(|&other| other == pad) {
+        CYCLES[index].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Called by the sampler after every round.
+pub fn sampled() { SAMPLES.fetch_add(1, Ordering::Relaxed); }
+
+/// The sampler's count, then the count of each of `pads`.
+fn progress(pads: &[Pad]) -> Vec<usize> {
+    let mut counts = vec![SAMPLES.load(Ordering::Relaxed)];
+    counts.extend(pads.iter().filter_map(|&pad| {
+        let index = PADS.iter().position(|&other| other == pad)?;
+        Some(CYCLES[index].load(Ordering::Relaxed))
+    }));
+    counts
+}
+
+/// Beats in a row a worker with PWM cycles of `period` may go without
+/// finishing one: two of its cycles, however slow, but at least a beat.
+fn patience(period: Duration) -> u128 {
+    (2 * period.as_millis()).div_ceil(BEAT.as_millis()).max(1)
+}
+
+/// Beats twice in quick succession every few seconds, for as long as the
+/// sampler and the workers of `watched` keep making progress, and blinks
+/// fast as soon as one of them stops, while its `page` is up. Each worker
+/// comes with its PWM period, since it only counts whole cycles.
+pub fn spawn_heartbeat(
+    txs: Vec<Sender<Show>>,
+    watched: &[(Pad, Duration)],
+    page: usize,
+) {
+    let pads: Vec<Pad> = watched.iter().map(|&(pad, _)| pad).collect();
+    // The sampler's first, and gets a round done every beat.
+    let patience: Vec<u128> = std::iter::once(1)
+        .chain(watched.iter().map(|&(_, period)| patience(period)))
+        .collect();
+    let beat: Arc<[Segment]> =
+        [(true, 100), (false, 150), (true, 100), (false, 2650)]
+            .iter()
+            .map(|&(lit, millis)| Segment {
+                lit,
+                length: Duration::from_millis(millis),
+            })
+            .collect();
+    // Left running when shutting down, like the sampler.
+    spawn(move || {
+        let mut last = progress(&pads);
+        let mut missed = vec![0; last.len()];
+        loop {
+            sleep(BEAT);
+            let now = progress(&pads);
+            for ((now, then), missed) in now.iter().zip(&last).zip(&mut missed)
+            {
+                *missed = if now == then { *missed + 1 } else { 0 };
+            }
+            let healthy = missed
+                .iter()
+                .zip(&patience)
+                .all(|(missed, patience)| missed < patience);
+            last = now;
+            let show = if healthy {
+                Show::Morse(Arc::clone(&beat))
+            } else {
+                Show::Blink { hz: DISTRESS_HZ }
+            };
+            if !paging::showing(page) {
+                continue;
+            }
+            if !txs.iter().all(|tx| tx.send(show.clone()).is_ok()) {
+                break;
+            }
+        }
+    });
+}